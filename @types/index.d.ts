@@ -36,36 +36,189 @@ declare global {
 	namespace Nvmd {
 		interface Version {
 			version: string;
-			npm: string;
+			npm?: string;
 			lts: string | false;
 			date: string;
-			v8: string;
+			v8?: string;
+			uv?: string;
+			zlib?: string;
+			openssl?: string;
+			modules?: string;
+			security: boolean;
 			files: string[];
 		}
 
 		type Versions = Array<Version>;
 
-		interface ProgressData {
-			source: 'download' | 'unzip';
-			transferred: number;
-			total: number;
+		interface VersionList {
+			list: Versions;
+			// index base url of the mirror the list came from
+			mirror?: string;
+			// when it was fetched or last revalidated (seconds since the unix epoch)
+			fetched_at?: number;
+			// seconds since `fetched_at`
+			age?: number;
+			// older than `index_ttl`, or never fetched
+			stale: boolean;
 		}
 
+		type Prefer = 'installed' | 'remote';
+
+		interface Transfer {
+			done: number;
+			// `null` when unknown, e.g. a chunked response
+			total: number | null;
+			// per second
+			rate: number;
+			// seconds left
+			eta: number | null;
+		}
+
+		// `extracting` counts the compressed bytes of a tarball & the entries of a zip
+		type Progress =
+			| { phase: 'resolving'; mirror: string }
+			| { phase: 'connecting'; url: string }
+			| ({ phase: 'downloading' } & Transfer)
+			| { phase: 'verifying' }
+			| ({ phase: 'extracting' } & Transfer)
+			| { phase: 'finalizing' }
+			// `delay` in milliseconds
+			| { phase: 'retrying'; attempt: number; retries: number; delay: number };
+
+		// a progress event of install job `job`
+		type ProgressData = { job: number; version: string } & Progress;
+
+		// `download`: into the archive cache only
+		type JobKind = 'install' | 'download';
+
+		type JobStatus = 'queued' | 'running';
+
+		interface Job {
+			id: number;
+			kind: JobKind;
+			version: string;
+			arch: string | null;
+			status: JobStatus;
+			// KB/s, on top of the `download_limit` setting
+			download_limit: number | null;
+		}
+
+		type ProxyScheme = 'http' | 'https' | 'socks5' | 'socks5h';
+
+		// manual: ip & port, system: the environment & OS settings, pac: the PAC file at `pac`
+		type ProxyMode = 'manual' | 'system' | 'pac';
+
 		interface Proxy {
 			enabled: boolean;
+			mode?: ProxyMode;
+			scheme?: ProxyScheme;
 			ip?: string;
 			port?: string;
+			username?: string;
+			// write only, kept in the system keychain, an empty string removes it
+			password?: string;
+			bypass?: string[];
+			// url or path of the PAC file
+			pac?: string;
+		}
+
+		interface Tls {
+			// PEM files of extra CA certificates, e.g. a corporate CA
+			ca_files?: string[];
+			// trust the CA certificates of the OS store too
+			native_roots?: boolean;
+		}
+
+		type SignatureMode = 'require' | 'warn' | 'skip';
+
+		type SignatureStatus =
+			| { status: 'verified'; fingerprint: string }
+			| { status: 'unverified'; reason: string }
+			| { status: 'skipped' };
+
+		interface InstallResult {
+			path: string;
+			signature: SignatureStatus;
+			mirror: string;
+		}
+
+		interface Mirror {
+			url: string;
+			index_url?: string;
+			download_url?: string;
+			// dangerous: the TLS certificate of the mirror is not verified
+			insecure?: boolean;
+			// write only, kept in the system keychain, an empty object removes them
+			credentials?: MirrorCredentials;
+		}
+
+		interface MirrorCredentials {
+			// basic auth
+			username?: string;
+			password?: string;
+			// bearer token, used instead of the basic auth
+			token?: string;
+			// extra headers, e.g. `X-JFrog-Art-Api`
+			headers?: Record<string, string>;
+		}
+
+		interface MirrorBenchmark {
+			mirror: Mirror;
+			elapsed?: number;
+			error?: string;
+		}
+
+		type LeftoverKind = 'archive' | 'staging' | 'unrenamed';
+
+		interface Leftover {
+			kind: LeftoverKind;
+			path: string;
+			size: number;
+			version?: string;
+			finishable: boolean;
+		}
+
+		interface CacheEntry {
+			mirror: string;
+			version: string;
+			filename: string;
+			path: string;
+			size: number;
+			sha256: string;
+			signature: SignatureStatus;
+			created_at: number;
+			used_at: number;
 		}
 
 		interface Setting {
 			closer: Closer;
 			coder: string;
 			directory: string;
+			enable_cache?: boolean;
+			cache_limit?: number;
 			enable_silent_start?: boolean;
+			enable_streaming?: boolean;
+			enable_unofficial?: boolean;
+			// installs running at the same time, the others wait in the queue
+			install_concurrency?: number;
+			// download bandwidth shared by all the installs (KB/s), `0` for no limit
+			download_limit?: number;
 			locale: string;
 			mirror: string;
+			mirrors?: Array<Mirror>;
+			unofficial_mirror?: string;
 			proxy?: Proxy;
 			no_proxy?: boolean;
+			// seconds, `0` for no limit
+			connect_timeout?: number;
+			read_timeout?: number;
+			total_timeout?: number;
+			// retries of a request failing with 5xx, a reset connection or a timeout
+			retries?: number;
+			// hours before the version list is refreshed on launch
+			index_ttl?: number;
+			tls?: Tls;
+			signature?: SignatureMode;
 			theme: Themes;
 		}
 
@@ -97,6 +250,8 @@ declare global {
 			mirrors?: string;
 			projects?: boolean;
 			setting?: boolean;
+			// also export the mirror credentials (secrets)
+			credentials?: boolean;
 		}
 
 		interface ConfigrationImport {
@@ -0,0 +1,72 @@
+/**
+ * https://github.com/nodejs/release-keys
+ * Get the public keys of the Node.js release team and bundle them into the `get-node` crate.
+ * They are used to verify the signature of `SHASUMS256.txt` ("src-tauri/crates/get-node/keys/release-keys.asc")
+ */
+
+import fs from 'fs-extra';
+import path from 'node:path';
+import fetch from 'node-fetch';
+import { HttpsProxyAgent } from 'https-proxy-agent';
+
+const cwd = process.cwd();
+const RELEASE_KEYS_URL =
+  process.env.RELEASE_KEYS_URL ||
+  'https://raw.githubusercontent.com/nodejs/release-keys/HEAD';
+
+/**
+ * fetch text content from `url`
+ */
+async function fetchText(url) {
+  const options = {};
+
+  const httpProxy =
+    process.env.HTTP_PROXY ||
+    process.env.http_proxy ||
+    process.env.HTTPS_PROXY ||
+    process.env.https_proxy;
+
+  if (httpProxy) {
+    options.agent = new HttpsProxyAgent(httpProxy);
+  }
+
+  const response = await fetch(url, options);
+  if (!response.ok) {
+    throw new Error(`failed to fetch "${url}" (${response.status})`);
+  }
+
+  return response.text();
+}
+
+async function run() {
+  // `keys.list` contains the fingerprints of all active releasers
+  const list = await fetchText(`${RELEASE_KEYS_URL}/keys.list`);
+  const fingerprints = list
+    .split('\n')
+    .map((line) => line.trim())
+    .filter(Boolean);
+
+  const keys = [];
+  for (const fingerprint of fingerprints) {
+    keys.push(await fetchText(`${RELEASE_KEYS_URL}/keys/${fingerprint}.asc`));
+    console.log(`[INFO]: fetched release key "${fingerprint}"`);
+  }
+
+  const target = path.join(
+    cwd,
+    'src-tauri',
+    'crates',
+    'get-node',
+    'keys',
+    'release-keys.asc',
+  );
+  await fs.mkdirp(path.dirname(target));
+  await fs.writeFile(target, keys.map((key) => key.trim()).join('\n'));
+
+  console.log(`[INFO]: ${keys.length} release keys saved to "${target}"`);
+}
+
+run().catch((err) => {
+  console.error(`[ERROR]: ${err.message}`);
+  process.exit(1);
+});
@@ -0,0 +1,9 @@
+fn main() {
+    println!("cargo:rerun-if-changed=keys/release-keys.asc");
+
+    // `SHASUMS256.txt` can not be verified without the release keys, see `archive::signature`
+    let keys = std::fs::read_to_string("keys/release-keys.asc").unwrap_or_default();
+    if keys.trim().is_empty() {
+        panic!("keys/release-keys.asc is empty, no signature could be verified: run `pnpm release-keys`");
+    }
+}
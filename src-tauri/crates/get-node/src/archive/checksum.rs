@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use sha2::{Digest, Sha256};
+use tokio::{fs::File, io::AsyncReadExt};
+
+use super::send;
+use crate::Error;
+use crate::{net::Retrier, transport::Transport};
+
+/// The checksum file published alongside every Node release
+pub const SHASUMS_FILENAME: &str = "SHASUMS256.txt";
+
+/// Download `SHASUMS256.txt` from the mirror directory of `version`
+pub(super) async fn fetch_shasums(
+    transport: &dyn Transport,
+    retrier: &Retrier<'_>,
+    mirror: &str,
+    version: &str,
+    cancel_signal: Option<&mut tokio::sync::watch::Receiver<bool>>,
+) -> Result<String> {
+    let url = format!("{}/v{}/{}", mirror, version, SHASUMS_FILENAME);
+    let response = send(transport, retrier, &url, cancel_signal).await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        bail!(format!(
+            "Failed to fetch {SHASUMS_FILENAME} ({status}), unable to verify the archive"
+        ));
+    }
+
+    Ok(response.text().await?)
+}
+
+/// Get the first of `filenames` listed in the content of `SHASUMS256.txt` with its expected sha256
+pub(super) fn expected_checksum(shasums: &str, filenames: &[String]) -> Result<(String, String)> {
+    match filenames.iter().find_map(|filename| {
+        find_checksum(shasums, filename).map(|checksum| (filename.clone(), checksum.to_string()))
+    }) {
+        Some(ret) => Ok(ret),
+        None => Err(Error::ArchiveNotFound {
+            filenames: filenames.to_vec(),
+        }
+        .into()),
+    }
+}
+
+/// Look up the checksum of `filename` in the content of a `SHASUMS256.txt` file
+///
+/// Every line has the shape `<sha256>  <filename>`
+pub(super) fn find_checksum<'a>(shasums: &'a str, filename: &str) -> Option<&'a str> {
+    shasums.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let checksum = parts.next()?;
+        // `sha256sum` prefixes the name with `*` in binary mode
+        let name = parts.next()?.trim_start_matches('*');
+        (name == filename).then_some(checksum)
+    })
+}
+
+/// Feed the content of the file to the hasher
+pub(super) async fn hash_file(path: &Path, hasher: &mut Sha256) -> Result<()> {
+    let mut file = File::open(path).await?;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let size = file.read(&mut buffer).await?;
+        if size == 0 {
+            break;
+        }
+        hasher.update(&buffer[..size]);
+    }
+
+    Ok(())
+}
+
+/// Compute the sha256 of the file as a lowercase hex string
+pub(crate) async fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hash_file(path, &mut hasher).await?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Compare the sha256 of a downloaded archive against the expected one
+pub(super) fn verify(filename: &str, expected: &str, actual: &str) -> Result<()> {
+    if !expected.eq_ignore_ascii_case(actual) {
+        return Err(Error::ChecksumMismatch {
+            file: filename.to_string(),
+            expected: expected.to_lowercase(),
+            actual: actual.to_lowercase(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHASUMS: &str = "\
+8a5ca2a0d5ce5e1c1fa1e9e8a8de0c6ab53a4a1a2d4e2fd0a1c7e6a0b3f9c1d2  node-v20.11.0-darwin-arm64.tar.gz
+0c4cb2a3c0d47e8f6bbf0a2d3f36d2b1b9c0e4b3ed3b5e9f5a0a3f7e2d1c4b5a  node-v20.11.0-linux-x64.tar.gz
+f6bbf0a2d3f36d2b1b9c0e4b3ed3b5e9f5a0a3f7e2d1c4b5a0c4cb2a3c0d47e8 *node-v20.11.0-win-x64.zip
+";
+
+    #[test]
+    fn test_find_checksum() {
+        assert_eq!(
+            find_checksum(SHASUMS, "node-v20.11.0-linux-x64.tar.gz"),
+            Some("0c4cb2a3c0d47e8f6bbf0a2d3f36d2b1b9c0e4b3ed3b5e9f5a0a3f7e2d1c4b5a")
+        );
+        assert_eq!(
+            find_checksum(SHASUMS, "node-v20.11.0-win-x64.zip"),
+            Some("f6bbf0a2d3f36d2b1b9c0e4b3ed3b5e9f5a0a3f7e2d1c4b5a0c4cb2a3c0d47e8")
+        );
+        assert_eq!(find_checksum(SHASUMS, "node-v20.11.0-linux-x64"), None);
+    }
+
+    #[test]
+    fn test_expected_checksum() {
+        let filenames = vec![
+            "node-v20.11.0-linux-x64.tar.xz".to_string(),
+            "node-v20.11.0-linux-x64.tar.gz".to_string(),
+        ];
+        // fall back to gzip when xz is not listed
+        assert_eq!(
+            expected_checksum(SHASUMS, &filenames).unwrap(),
+            (
+                "node-v20.11.0-linux-x64.tar.gz".to_string(),
+                "0c4cb2a3c0d47e8f6bbf0a2d3f36d2b1b9c0e4b3ed3b5e9f5a0a3f7e2d1c4b5a".to_string()
+            )
+        );
+        let err = expected_checksum(SHASUMS, &filenames[..1]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Error>(),
+            Some(&Error::ArchiveNotFound {
+                filenames: filenames[..1].to_vec()
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_mismatch() {
+        assert!(verify("node.tar.gz", "ABCDEF", "abcdef").is_ok());
+
+        let err = verify("node.tar.gz", "abcdef", "123456").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Error>(),
+            Some(&Error::ChecksumMismatch {
+                file: "node.tar.gz".to_string(),
+                expected: "abcdef".to_string(),
+                actual: "123456".to_string(),
+            })
+        );
+    }
+}
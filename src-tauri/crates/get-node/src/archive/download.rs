@@ -0,0 +1,410 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use futures_util::StreamExt;
+use reqwest::{
+    header::{HeaderValue, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE},
+    Response, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::{remove_file, rename, File, OpenOptions},
+    io::AsyncWriteExt,
+};
+
+use super::{checksum, OnProgress, Remote};
+use crate::{
+    net::{self, RateLimit, Retrier},
+    progress::{Meter, Progress},
+    transport,
+};
+
+/// Suffix of the partially downloaded archive
+pub const PARTIAL_SUFFIX: &str = ".part";
+
+/// Suffix of the metadata saved next to the partial archive
+pub const PARTIAL_META_SUFFIX: &str = ".part.json";
+
+/// What we need to know to safely resume a partial download
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+struct PartialMeta {
+    /// the url the partial content was downloaded from
+    url: String,
+
+    /// strong `ETag` of the remote file
+    etag: Option<String>,
+
+    /// `Last-Modified` of the remote file
+    last_modified: Option<String>,
+
+    /// total size of the remote file, `None` when it was not announced (chunked response)
+    total_size: Option<u64>,
+}
+
+impl PartialMeta {
+    /// the validator sent with `If-Range`
+    /// weak etags are not allowed there
+    fn validator(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+}
+
+/// The validator of `response` to send with `If-Range` when resuming it
+/// weak etags are not allowed there
+pub(super) fn if_range(response: &Response) -> Option<String> {
+    let header = |name| response.headers().get(name)?.to_str().ok();
+    header(ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or(header(LAST_MODIFIED))
+        .map(|value| value.to_string())
+}
+
+/// Whether the server honored the range, it answers `206` starting at `offset`
+pub(super) fn resumes(response: &Response, offset: u64) -> bool {
+    response.status() == StatusCode::PARTIAL_CONTENT
+        && response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with(&format!("bytes {offset}-")))
+}
+
+pub(super) fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+async fn read_meta(path: &Path) -> Option<PartialMeta> {
+    let content = tokio::fs::read(path).await.ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+async fn remove_partial(part_path: &Path, meta_path: &Path) {
+    let _ = tokio::join!(remove_file(part_path), remove_file(meta_path));
+}
+
+/// Download the archive of `remote` to `path` and verify it against the expected sha256
+///
+/// The content is streamed to `<path>.part` and hashed on the fly, with the metadata
+/// in `<path>.part.json`. If the download fails or is cancelled, both are kept and the
+/// next call resumes with `Range` / `If-Range`, falling back to a full download when the
+/// server does not support it or the remote file has changed.
+/// The chunks are written no faster than `rate_limit` lets them through.
+/// A body broken off midway (reset connection, read timeout) is resumed the same way
+/// after a backoff, up to `retrier.retry.retries` times.
+pub(super) async fn download(
+    remote: &Remote,
+    retrier: &Retrier<'_>,
+    path: &Path,
+    rate_limit: Option<&RateLimit>,
+    mut cancel_signal: Option<&mut tokio::sync::watch::Receiver<bool>>,
+    on_progress: &OnProgress,
+) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        let ret = download_once(
+            remote,
+            retrier,
+            path,
+            rate_limit,
+            cancel_signal.as_deref_mut(),
+            on_progress,
+        )
+        .await;
+        match ret {
+            Err(err) if attempt < retrier.retry.retries && net::is_transient_body_error(&err) => {
+                attempt += 1;
+                let delay = retrier.retry.delay(attempt);
+                retrier
+                    .backoff(attempt, delay, cancel_signal.as_deref_mut())
+                    .await?;
+            }
+            ret => return ret,
+        }
+    }
+}
+
+async fn download_once(
+    remote: &Remote,
+    retrier: &Retrier<'_>,
+    path: &Path,
+    rate_limit: Option<&RateLimit>,
+    mut cancel_signal: Option<&mut tokio::sync::watch::Receiver<bool>>,
+    on_progress: &OnProgress,
+) -> Result<()> {
+    let url = remote.url.as_str();
+    let part_path = with_suffix(path, PARTIAL_SUFFIX);
+    let meta_path = with_suffix(path, PARTIAL_META_SUFFIX);
+
+    // only resume what was downloaded from the same url with a usable validator
+    let meta = read_meta(&meta_path)
+        .await
+        .filter(|meta| meta.url == url && meta.validator().is_some());
+    let partial_size = match (&meta, tokio::fs::metadata(&part_path).await) {
+        (Some(meta), Ok(file)) if meta.total_size.is_some_and(|total| file.len() < total) => {
+            file.len()
+        }
+        _ => 0,
+    };
+
+    let mut request = transport::get(url)?;
+    if let (Some(meta), true) = (&meta, partial_size > 0) {
+        let headers = request.headers_mut();
+        headers.insert(
+            RANGE,
+            HeaderValue::from_str(&format!("bytes={partial_size}-"))?,
+        );
+        headers.insert(
+            IF_RANGE,
+            HeaderValue::from_str(meta.validator().unwrap_or_default())?,
+        );
+    }
+    on_progress(&Progress::Connecting {
+        url: url.to_string(),
+    });
+    let response = net::send(
+        &*remote.transport,
+        request,
+        retrier,
+        cancel_signal.as_deref_mut(),
+    )
+    .await?;
+
+    let status = response.status();
+    if status == StatusCode::RANGE_NOT_SATISFIABLE {
+        // the partial file is unusable, start over next time
+        remove_partial(&part_path, &meta_path).await;
+        bail!(format!("HTTP failure ({status})"));
+    }
+    if !status.is_success() {
+        bail!(format!("HTTP failure ({status})"));
+    }
+
+    // unknown with a chunked response
+    let content_length = transport::content_length(&response);
+
+    let resumed = resumes(&response, partial_size);
+
+    let mut hasher = Sha256::new();
+    let (mut file, offset) = if resumed {
+        checksum::hash_file(&part_path, &mut hasher).await?;
+        let file = OpenOptions::new().append(true).open(&part_path).await?;
+        (file, partial_size)
+    } else {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+        let meta = PartialMeta {
+            url: url.to_string(),
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            total_size: content_length,
+        };
+        tokio::fs::write(&meta_path, serde_json::to_vec(&meta)?).await?;
+        (File::create(&part_path).await?, 0)
+    };
+
+    let meter = Meter::new(offset, content_length.map(|length| offset + length));
+    let mut downloaded_size = offset;
+    on_progress(&Progress::Downloading(meter.transfer(downloaded_size)));
+
+    let mut stream = response.bytes_stream();
+    // write stream buffer to file
+    while let Some(chunk) = match cancel_signal.as_deref_mut() {
+        Some(cancel_receiver) => {
+            tokio::select! {
+                chunk = stream.next() => {
+                    chunk
+                },
+                _ = cancel_receiver.changed() => {
+                    // keep the partial file so the download can be resumed
+                    file.flush().await?;
+                    bail!("Download was cancelled");
+                }
+            }
+        }
+        None => stream.next().await,
+    } {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                file.flush().await?;
+                return Err(err.into());
+            }
+        };
+        if let Err(err) = net::throttle(rate_limit, chunk.len(), cancel_signal.as_deref_mut()).await
+        {
+            file.flush().await?;
+            return Err(err);
+        }
+        downloaded_size += chunk.len() as u64;
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+        on_progress(&Progress::Downloading(meter.transfer(downloaded_size)));
+    }
+    file.sync_all().await?;
+    drop(file);
+
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    on_progress(&Progress::Verifying);
+    let actual = format!("{:x}", hasher.finalize());
+    if let Err(err) = checksum::verify(&filename, &remote.expected, &actual) {
+        remove_partial(&part_path, &meta_path).await;
+        return Err(err);
+    }
+
+    rename(&part_path, path).await?;
+    let _ = remove_file(&meta_path).await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use reqwest::header::CONTENT_LENGTH;
+    use std::{
+        io::ErrorKind,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use crate::{
+        archive::SignatureStatus,
+        net::Retry,
+        transport::{Sending, Transport},
+    };
+
+    pub(in crate::archive) const URL: &str =
+        "https://mock.invalid/node/v20.11.1/node-v20.11.1-linux-x64.tar.gz";
+
+    pub(in crate::archive) fn retrier(retries: u32) -> Retrier<'static> {
+        let retry = Retry {
+            retries,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        };
+        Retrier::new(Some(retry), None)
+    }
+
+    /// A mirror whose first response breaks off after `cut` bytes, the ranged ones do not
+    pub(in crate::archive) struct Flaky {
+        data: Vec<u8>,
+        cut: usize,
+
+        /// the `Range` of every request received
+        pub(in crate::archive) ranges: Mutex<Vec<Option<String>>>,
+    }
+
+    impl Flaky {
+        pub(in crate::archive) fn new(data: Vec<u8>, cut: usize) -> Arc<Self> {
+            Arc::new(Flaky {
+                data,
+                cut,
+                ranges: Mutex::new(Vec::new()),
+            })
+        }
+    }
+
+    impl Transport for Flaky {
+        fn send(&self, request: reqwest::Request) -> Sending<'_> {
+            let range = request
+                .headers()
+                .get(RANGE)
+                .map(|value| value.to_str().unwrap().to_string());
+            self.ranges.lock().unwrap().push(range.clone());
+
+            let size = self.data.len();
+            let start = range.as_deref().and_then(|range| {
+                range
+                    .strip_prefix("bytes=")?
+                    .strip_suffix('-')?
+                    .parse()
+                    .ok()
+            });
+            let response = match start {
+                Some(start) => {
+                    assert_eq!(request.headers()[IF_RANGE], "\"v1\"");
+                    http::Response::builder()
+                        .status(StatusCode::PARTIAL_CONTENT)
+                        .header(CONTENT_RANGE, format!("bytes {start}-{}/{size}", size - 1))
+                        .header(CONTENT_LENGTH, size - start)
+                        .body(reqwest::Body::from(self.data[start..].to_vec()))
+                }
+                None => {
+                    let chunks: Vec<std::io::Result<Bytes>> = vec![
+                        Ok(Bytes::copy_from_slice(&self.data[..self.cut])),
+                        Err(ErrorKind::ConnectionReset.into()),
+                    ];
+                    http::Response::builder()
+                        .header(ETAG, "\"v1\"")
+                        .header(CONTENT_LENGTH, size)
+                        .body(reqwest::Body::wrap_stream(futures_util::stream::iter(
+                            chunks,
+                        )))
+                }
+            };
+            Box::pin(async move { Ok(response?.into()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_broken_body() {
+        let data = b"node".repeat(1000);
+        let root = std::env::temp_dir().join(format!("get-node-download-{}", std::process::id()));
+        let _ = tokio::fs::remove_dir_all(&root).await;
+        tokio::fs::create_dir_all(&root).await.unwrap();
+
+        for retries in [0, 1] {
+            let flaky = Flaky::new(data.clone(), 1500);
+            let remote = Remote {
+                transport: flaky.clone(),
+                mirror: "https://mock.invalid/node".to_string(),
+                url: URL.to_string(),
+                name: "node-v20.11.1-linux-x64".to_string(),
+                full_name: "node-v20.11.1-linux-x64.tar.gz".to_string(),
+                expected: format!("{:x}", Sha256::digest(&data)),
+                signature: SignatureStatus::Skipped,
+            };
+            let path = root.join(format!("{retries}-{}", remote.full_name));
+            let ret = download(
+                &remote,
+                &retrier(retries),
+                &path,
+                None,
+                None,
+                &|_: &Progress| {},
+            )
+            .await;
+
+            if retries == 0 {
+                // kept for the next fetch to resume
+                assert!(ret.is_err());
+                let part = tokio::fs::read(with_suffix(&path, PARTIAL_SUFFIX)).await;
+                assert_eq!(part.unwrap().len(), 1500);
+                continue;
+            }
+            ret.unwrap();
+            assert_eq!(tokio::fs::read(&path).await.unwrap(), data);
+            assert_eq!(
+                *flaky.ranges.lock().unwrap(),
+                vec![None, Some("bytes=1500-".to_string())]
+            );
+        }
+
+        let _ = tokio::fs::remove_dir_all(&root).await;
+    }
+}
@@ -1,28 +1,59 @@
+mod checksum;
+mod download;
+mod signature;
+mod stage;
 mod tarball;
 mod zip;
 
-use super::{node, Proxy};
-use anyhow::Result;
-use std::{path::PathBuf, time::Duration};
+use super::{
+    cache,
+    cache::CacheEntry,
+    mirror::{self, Endpoint, Failures, Mirror},
+    net::{self, RateLimit, Retrier, Retry, Timeouts},
+    node,
+    platform::{Ext, Platform},
+    progress::Progress,
+    proxy::{self, Proxy},
+    tls::Tls,
+    transport::{self, Transport},
+};
+use anyhow::{bail, Result};
+use download::download;
+use node_semver::Version;
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::fs::remove_file;
 
-/// get progress
-/// source: &str (`download` & `unzip`)
-/// completed size
-/// total size
-pub type OnProgress = dyn Fn(&str, usize, usize) + Send + Sync;
+pub use crate::progress::OnProgress;
+pub(crate) use checksum::sha256_file;
+pub use checksum::SHASUMS_FILENAME;
+pub use download::{PARTIAL_META_SUFFIX, PARTIAL_SUFFIX};
+pub use signature::{SignatureMode, SignatureStatus};
+pub(crate) use stage::validate;
+pub use stage::STAGING_PREFIX;
+pub use tarball::untar;
+pub use zip::unzip;
 
 pub struct FetchConfig {
     /// output dir
     pub dest: String,
 
-    /// fetch mirror url
-    pub mirror: String,
+    /// fetch mirrors, tried in turn until one of them serves the archive
+    pub mirrors: Vec<Mirror>,
+
+    /// unofficial builds mirrors (`unofficial-builds.nodejs.org`), `None` to never use them
+    /// used for musl, riscv64, loong64 & old glibc, or when the official mirrors have no archive
+    pub unofficial: Option<Vec<Mirror>>,
 
     /// node version
     pub version: String,
 
-    // system arch
-    pub arch: Option<String>,
+    /// the target platform of the archive, `None` for `Platform::current()`
+    /// another platform's archive can be fetched too, e.g. Windows zips on Linux
+    pub platform: Option<Platform>,
 
     /// proxy ip & port
     pub proxy: Option<Proxy>,
@@ -30,85 +61,410 @@ pub struct FetchConfig {
     /// disable proxy
     pub no_proxy: Option<bool>,
 
-    /// timeout
-    pub timeout: Option<Duration>,
+    /// connect, read-idle & total timeouts
+    /// default value is `Timeouts::default()`
+    pub timeouts: Option<Timeouts>,
+
+    /// how the requests are retried on transient failures, reported as `retry` progress
+    /// default value is `Retry::default()`
+    pub retry: Option<Retry>,
+
+    /// extra CA certificates & OS trust store
+    pub tls: Option<Tls>,
+
+    /// how the requests are sent, `None` to pick it for each mirror:
+    /// `FileTransport` for a `file://` url or a local directory, an http client otherwise
+    pub transport: Option<Arc<dyn Transport>>,
+
+    /// how to handle the signature of `SHASUMS256.txt`
+    /// default value is `SignatureMode::Warn`
+    pub signature: Option<SignatureMode>,
+
+    /// archive cache dir
+    /// archives are installed from & kept in it, `None` to remove them after installing
+    pub cache_dir: Option<String>,
+
+    /// unpack tarballs while downloading them, without a temp file
+    /// downloads can not be resumed in this mode, zip archives ignore it
+    pub streaming: Option<bool>,
+
+    /// the bandwidth limit of the download, shared with other downloads when cloned
+    /// see `RateLimit::child` to combine a global limit with a per-download one
+    pub rate_limit: Option<RateLimit>,
 
     /// to cancel fetch
     pub cancel_signal: Option<tokio::sync::watch::Receiver<bool>>,
 
-    /// progress callback
+    /// progress callback, see `progress::subscribe` for a `Stream` of the events
     pub on_progress: Box<OnProgress>,
 }
 
-fn create_client(
-    proxy: Option<Proxy>,
-    no_proxy: Option<bool>,
-    timeout: Duration,
-) -> Result<reqwest::Client> {
-    let mut builder = reqwest::ClientBuilder::new()
-        .use_rustls_tls()
-        .timeout(timeout);
-    if let Some(true) = no_proxy {
-        builder = builder.no_proxy();
-    } else if let Some(proxy) = proxy {
-        if proxy.enabled {
-            builder = builder.no_proxy();
-            let proxy_scheme = format!("http://{}:{}", proxy.ip, proxy.port);
-            if let Ok(proxy) = reqwest::Proxy::http(&proxy_scheme) {
-                builder = builder.proxy(proxy);
-            }
-            if let Ok(proxy) = reqwest::Proxy::https(&proxy_scheme) {
-                builder = builder.proxy(proxy);
+#[derive(Debug, Clone, Serialize)]
+pub struct FetchResult {
+    /// the installed node directory
+    pub path: String,
+
+    /// the signature verification result of `SHASUMS256.txt`
+    pub signature: SignatureStatus,
+
+    /// download url of the mirror the archive came from
+    pub mirror: String,
+}
+
+/// The archive to unpack
+struct Obtained {
+    /// archive path
+    path: PathBuf,
+
+    /// the top level directory inside the archive
+    name: String,
+
+    /// the signature verification result of `SHASUMS256.txt`
+    signature: SignatureStatus,
+
+    /// the cache entry, if the archive is kept in the cache
+    entry: Option<CacheEntry>,
+
+    /// download url of the mirror the archive came from
+    mirror: String,
+}
+
+impl Obtained {
+    /// Remove the archive unless it is kept in the cache
+    async fn cleanup(&self) -> std::io::Result<()> {
+        match self.entry {
+            Some(_) => Ok(()),
+            None => remove_file(&self.path).await,
+        }
+    }
+}
+
+/// The archive resolved from `SHASUMS256.txt`, ready to be downloaded
+struct Remote {
+    transport: Arc<dyn Transport>,
+
+    /// download url of the mirror
+    mirror: String,
+
+    /// archive url
+    url: String,
+
+    /// the top level directory inside the archive
+    name: String,
+
+    /// archive filename
+    full_name: String,
+
+    /// the expected sha256 of the archive
+    expected: String,
+
+    /// the signature verification result of `SHASUMS256.txt`
+    signature: SignatureStatus,
+}
+
+/// A mirror to fetch the archive from
+struct Source {
+    mirror: Mirror,
+
+    /// an unofficial build only tried when the official mirrors have no archive
+    fallback: bool,
+}
+
+/// The sources to try in turn
+fn sources(config: &FetchConfig) -> Result<Vec<Source>> {
+    let version = Version::parse(&config.version)?;
+    let platform = platform(config);
+    let unofficial = config.unofficial.as_deref().unwrap_or_default();
+    let source = |mirror, fallback| Source { mirror, fallback };
+
+    // the official builds do not run on this platform
+    if platform.is_unofficial(&version) {
+        if config.unofficial.is_none() {
+            bail!(
+                "Node v{version} for \"{}\" is only published as an unofficial build, \
+                 enable unofficial builds to install it",
+                platform.archive_arch(&version)
+            );
+        }
+        return Ok(mirror::ordered(unofficial, Endpoint::Download)
+            .into_iter()
+            .map(|mirror| source(mirror, false))
+            .collect());
+    }
+
+    let official = mirror::ordered(&config.mirrors, Endpoint::Download)
+        .into_iter()
+        .map(|mirror| source(mirror, false));
+    let fallback = mirror::ordered(unofficial, Endpoint::Download)
+        .into_iter()
+        .map(|mirror| source(mirror, true));
+
+    Ok(official.chain(fallback).collect())
+}
+
+/// The target platform of the archive
+fn platform(config: &FetchConfig) -> Platform {
+    config.platform.clone().unwrap_or_else(Platform::current)
+}
+
+/// Get the archive from the cache when there is a valid entry
+/// an entry whose signature does not satisfy `config.signature` is downloaded again
+async fn cached(config: &FetchConfig) -> Result<Option<Obtained>> {
+    let Some(cache_dir) = config.cache_dir.as_deref().map(Path::new) else {
+        return Ok(None);
+    };
+
+    let mode = config.signature.unwrap_or_default();
+    let version = Version::parse(&config.version)?;
+    let (name, full_names) = node::Node::archive_filenames(&version, &platform(config));
+    for source in sources(config)? {
+        let mirror = source.mirror.download_base();
+        for full_name in &full_names {
+            let entry = cache::lookup(cache_dir, mirror, full_name).await;
+            if let Some(entry) = entry.filter(|entry| entry.signature.satisfies(mode)) {
+                return Ok(Some(Obtained {
+                    path: PathBuf::from(&entry.path),
+                    name: name.clone(),
+                    signature: entry.signature.clone(),
+                    mirror: entry.mirror.clone(),
+                    entry: Some(entry),
+                }));
             }
-            if let Ok(proxy) = reqwest::Proxy::all(&proxy_scheme) {
-                builder = builder.proxy(proxy);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Whether the fetch has been cancelled, so that no other mirror should be tried
+fn is_cancelled(config: &FetchConfig) -> bool {
+    config
+        .cancel_signal
+        .as_ref()
+        .is_some_and(|cancel_receiver| *cancel_receiver.borrow())
+}
+
+/// Resolve the archive to download from `source`
+async fn resolve(config: &mut FetchConfig, source: &Source) -> Result<Remote> {
+    let (name, full_names) =
+        node::Node::archive_filenames(&Version::parse(&config.version)?, &platform(config));
+    let base = source.mirror.download_base();
+    (config.on_progress)(&Progress::Resolving {
+        mirror: base.to_string(),
+    });
+
+    let transport = transport::for_mirror(
+        config.transport.as_ref(),
+        config.proxy.clone(),
+        config.no_proxy,
+        config.tls.as_ref(),
+        &source.mirror,
+        Endpoint::Download,
+        config.timeouts.unwrap_or_default(),
+    )
+    .await?;
+    let retrier = Retrier::new(config.retry, Some(&config.on_progress));
+
+    // fetch the expected checksum first, so that a broken mirror fails before downloading
+    // the archive format is picked from what `SHASUMS256.txt` lists
+    let (full_name, expected, signature) = fetch_checksum(
+        &*transport,
+        &retrier,
+        base,
+        &config.version,
+        &full_names,
+        config.signature.unwrap_or_default(),
+        config.cancel_signal.as_mut(),
+    )
+    .await?;
+
+    Ok(Remote {
+        transport,
+        mirror: base.to_string(),
+        url: format!("{}/v{}/{}", base, &config.version, &full_name),
+        name,
+        full_name,
+        expected,
+        signature,
+    })
+}
+
+/// Download the archive from the first mirror that serves it (into the cache if enabled)
+async fn download_archive(config: &mut FetchConfig) -> Result<Obtained> {
+    let mut failures = Failures::default();
+    for source in sources(config)? {
+        // the unofficial builds only stand in for a missing official archive
+        if source.fallback && !failures.all_not_found() {
+            break;
+        }
+
+        let base = source.mirror.download_base();
+        match download_from(config, &source).await {
+            Ok(archive) => {
+                mirror::report_success(base);
+                return Ok(archive);
             }
+            Err(err) if is_cancelled(config) => return Err(err),
+            Err(err) => failures.push(base, err),
+        }
+    }
+
+    Err(failures.into_error())
+}
+
+/// Download the archive from `source` (into the cache if enabled)
+async fn download_from(config: &mut FetchConfig, source: &Source) -> Result<Obtained> {
+    let remote = resolve(config, source).await?;
+
+    let cache_dir = config.cache_dir.as_deref().map(Path::new);
+    let path = match cache_dir {
+        Some(cache_dir) => cache::prepare(cache_dir, &remote.mirror, &remote.full_name).await?,
+        None => PathBuf::from(&config.dest).join(&remote.full_name),
+    };
+    download(
+        &remote,
+        &Retrier::new(config.retry, Some(&config.on_progress)),
+        &path,
+        config.rate_limit.as_ref(),
+        config.cancel_signal.as_mut(),
+        &config.on_progress,
+    )
+    .await?;
+
+    let Remote {
+        mirror,
+        name,
+        expected,
+        signature,
+        ..
+    } = remote;
+    let entry = match cache_dir {
+        Some(_) => {
+            Some(cache::store(&path, &mirror, &config.version, &expected, &signature).await?)
         }
+        None => None,
+    };
+
+    Ok(Obtained {
+        path,
+        name,
+        signature,
+        entry,
+        mirror,
+    })
+}
+
+/// Get the archive from the cache when there is a valid entry,
+/// otherwise download it from the mirror (into the cache if enabled)
+async fn obtain(config: &mut FetchConfig) -> Result<Obtained> {
+    match cached(config).await? {
+        Some(archive) => Ok(archive),
+        None => download_archive(config).await,
+    }
+}
+
+/// Download the archive into the cache without installing it
+/// `cache_dir` is required, `dest` is ignored
+pub async fn prefetch(mut config: FetchConfig) -> Result<CacheEntry> {
+    if config.cache_dir.is_none() {
+        bail!("cache_dir should not be null");
+    }
+
+    match obtain(&mut config).await?.entry {
+        Some(entry) => Ok(entry),
+        None => bail!("Failed to save the archive to the cache"),
+    }
+}
+
+/// Create the http client of a mirror, with its credentials
+pub(crate) async fn create_client(
+    proxy: Option<Proxy>,
+    no_proxy: Option<bool>,
+    tls: Option<&Tls>,
+    mirror: &Mirror,
+    timeouts: Timeouts,
+) -> Result<reqwest::Client> {
+    // the proxy of each request is picked by the settings only, not by the environment
+    let mut builder = reqwest::ClientBuilder::new().use_rustls_tls().no_proxy();
+    if let Some(timeout) = timeouts.connect {
+        builder = builder.connect_timeout(timeout);
+    }
+    if let Some(timeout) = timeouts.read {
+        builder = builder.read_timeout(timeout);
+    }
+    if let Some(timeout) = timeouts.total {
+        builder = builder.timeout(timeout);
+    }
+    let targets = [mirror.index_base(), mirror.download_base()];
+    if let Some(proxy) = proxy::reqwest_proxy(proxy.as_ref(), no_proxy, &targets).await? {
+        builder = builder.proxy(proxy);
+    }
+    if let Some(tls) = tls {
+        builder = tls.apply(builder).await?;
+    }
+    if mirror.insecure {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    if let Some(credentials) = &mirror.credentials {
+        // `Authorization` is dropped by reqwest when redirected to another host
+        builder = builder.default_headers(credentials.headers()?);
     }
 
     Ok(builder.build()?)
 }
 
+/// Send a GET request, retried on transient failures
 async fn send(
-    client: &reqwest::Client,
+    transport: &dyn Transport,
+    retrier: &Retrier<'_>,
     url: &str,
     cancel_signal: Option<&mut tokio::sync::watch::Receiver<bool>>,
 ) -> Result<reqwest::Response> {
-    match cancel_signal {
-        Some(cancel_receiver) => {
-            tokio::select! {
-                response = client.get(url).send() => {
-                    response.map_err(Into::into)
-                },
-                _ = cancel_receiver.changed() => {
-                    anyhow::bail!("Download was cancelled");
-                }
-            }
-        }
-        None => client.get(url).send().await.map_err(Into::into),
-    }
+    net::send(transport, transport::get(url)?, retrier, cancel_signal).await
 }
 
-cfg_if::cfg_if! {
-    if #[cfg(unix)] {
-        /// Fetch a remote archive in the native OS-preferred format from the specified
-        /// URL and store its results at the specified file path.
-        ///
-        /// On Windows, the preferred format is zip. On Unixes, the preferred format
-        /// is tarball.
-        pub async fn fetch_native(config: FetchConfig) -> Result<String> {
-            tarball::fetch(config).await
-        }
-    } else if #[cfg(windows)] {
-        /// Fetch a remote archive in the native OS-preferred format from the specified
-        /// URL and store its results at the specified file path.
-        ///
-        /// On Windows, the preferred format is zip. On Unixes, the preferred format
-        /// is tarball.
-        pub async fn fetch_native(config: FetchConfig) -> Result<String> {
-            zip::fetch(config).await
-        }
-    } else {
-        compile_error!("Unsupported OS (expected 'unix' or 'windows').");
+/// Fetch `SHASUMS256.txt`, verify its signature according to `mode`
+/// and return the first of `filenames` it lists with its expected sha256
+async fn fetch_checksum(
+    transport: &dyn Transport,
+    retrier: &Retrier<'_>,
+    mirror: &str,
+    version: &str,
+    filenames: &[String],
+    mode: SignatureMode,
+    mut cancel_signal: Option<&mut tokio::sync::watch::Receiver<bool>>,
+) -> Result<(String, String, SignatureStatus)> {
+    let shasums = checksum::fetch_shasums(
+        transport,
+        retrier,
+        mirror,
+        version,
+        cancel_signal.as_deref_mut(),
+    )
+    .await?;
+    let signature = signature::verify_shasums(
+        transport,
+        retrier,
+        mirror,
+        version,
+        &shasums,
+        mode,
+        cancel_signal,
+    )
+    .await?;
+    let (filename, expected) = checksum::expected_checksum(&shasums, filenames)?;
+
+    Ok((filename, expected, signature))
+}
+
+/// Fetch a remote archive in the format of the target platform
+/// and store its results at the specified file path.
+///
+/// For Windows, the format is zip. For Unixes, the format is tarball.
+/// Both can be fetched & unpacked on any host.
+pub async fn fetch_native(config: FetchConfig) -> Result<FetchResult> {
+    match platform(&config).extensions().first() {
+        Some(Ext::Zip) => zip::fetch(config).await,
+        _ => tarball::fetch(config).await,
     }
 }
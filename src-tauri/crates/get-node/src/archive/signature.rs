@@ -0,0 +1,361 @@
+use std::{io::Cursor, sync::OnceLock};
+
+use anyhow::{anyhow, Result};
+use pgp::{
+    cleartext::CleartextSignedMessage,
+    types::{KeyTrait, PublicKeyTrait},
+    Deserializable, SignedPublicKey, StandaloneSignature,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{send, SHASUMS_FILENAME};
+use crate::{net::Retrier, transport::Transport, Error};
+
+/// The public keys of the Node.js release team (https://github.com/nodejs/release-keys)
+/// refresh it with `pnpm release-keys`
+const RELEASE_KEYS: &str = include_str!("../../keys/release-keys.asc");
+
+/// How to handle the signature of `SHASUMS256.txt`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureMode {
+    /// refuse to install when the signature can not be verified
+    Require,
+
+    /// install anyway, but report why the signature could not be verified
+    #[default]
+    Warn,
+
+    /// do not download or check the signature
+    Skip,
+}
+
+/// The result of the signature verification
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum SignatureStatus {
+    /// signed by one of the bundled release keys
+    Verified {
+        /// fingerprint of the primary key
+        fingerprint: String,
+    },
+
+    /// the signature could not be verified (only with `SignatureMode::Warn`)
+    Unverified { reason: String },
+
+    /// verification is disabled
+    Skipped,
+}
+
+impl SignatureStatus {
+    /// Whether an archive checked with this result may be installed with `mode`,
+    /// e.g. an archive cached while the signature was not required
+    pub(super) fn satisfies(&self, mode: SignatureMode) -> bool {
+        mode != SignatureMode::Require || matches!(self, SignatureStatus::Verified { .. })
+    }
+}
+
+/// Verify `shasums` (the content of `SHASUMS256.txt`) with its signature from the mirror
+///
+/// `SHASUMS256.txt.sig` (detached) is preferred, `SHASUMS256.txt.asc` (clearsigned) is the fallback.
+pub(super) async fn verify_shasums(
+    transport: &dyn Transport,
+    retrier: &Retrier<'_>,
+    mirror: &str,
+    version: &str,
+    shasums: &str,
+    mode: SignatureMode,
+    mut cancel_signal: Option<&mut tokio::sync::watch::Receiver<bool>>,
+) -> Result<SignatureStatus> {
+    if mode == SignatureMode::Skip {
+        return Ok(SignatureStatus::Skipped);
+    }
+
+    let base_url = format!("{}/v{}/{}", mirror, version, SHASUMS_FILENAME);
+    let signature =
+        match fetch_signatures(transport, retrier, &base_url, cancel_signal.as_deref_mut()).await {
+            Ok(signature) => signature,
+            Err(err)
+                if mode == SignatureMode::Require
+                    || cancel_signal.is_some_and(|cancel_receiver| *cancel_receiver.borrow()) =>
+            {
+                return Err(err)
+            }
+            // an unreachable signature only blocks the installs requiring it
+            Err(err) => {
+                return Ok(SignatureStatus::Unverified {
+                    reason: format!(
+                        "failed to download the signature of {SHASUMS_FILENAME}: {err}"
+                    ),
+                })
+            }
+        };
+    let ret = match signature {
+        Some(Signature::Detached(sig)) => {
+            release_keys().and_then(|keys| verify_detached(keys, &sig, shasums.as_bytes()))
+        }
+        Some(Signature::Cleartext(asc)) => {
+            release_keys().and_then(|keys| verify_cleartext(keys, &asc, shasums))
+        }
+        None => Err(anyhow!(
+            "no signature of {SHASUMS_FILENAME} found on the mirror"
+        )),
+    };
+
+    match ret {
+        Ok(fingerprint) => Ok(SignatureStatus::Verified { fingerprint }),
+        Err(err) => match mode {
+            SignatureMode::Require => Err(Error::InvalidSignature {
+                reason: err.to_string(),
+            }
+            .into()),
+            _ => Ok(SignatureStatus::Unverified {
+                reason: err.to_string(),
+            }),
+        },
+    }
+}
+
+/// A signature file of `SHASUMS256.txt`
+enum Signature {
+    /// `SHASUMS256.txt.sig`
+    Detached(Vec<u8>),
+
+    /// `SHASUMS256.txt.asc`
+    Cleartext(Vec<u8>),
+}
+
+/// Download the signature of `base_url` (`SHASUMS256.txt`), `None` when the mirror has none
+async fn fetch_signatures(
+    transport: &dyn Transport,
+    retrier: &Retrier<'_>,
+    base_url: &str,
+    mut cancel_signal: Option<&mut tokio::sync::watch::Receiver<bool>>,
+) -> Result<Option<Signature>> {
+    let detached = fetch_signature(
+        transport,
+        retrier,
+        &format!("{base_url}.sig"),
+        cancel_signal.as_deref_mut(),
+    )
+    .await?;
+    if let Some(sig) = detached {
+        return Ok(Some(Signature::Detached(sig)));
+    }
+
+    let cleartext = fetch_signature(
+        transport,
+        retrier,
+        &format!("{base_url}.asc"),
+        cancel_signal,
+    )
+    .await?;
+    Ok(cleartext.map(Signature::Cleartext))
+}
+
+/// Download a signature file, `None` when the mirror does not provide it
+async fn fetch_signature(
+    transport: &dyn Transport,
+    retrier: &Retrier<'_>,
+    url: &str,
+    cancel_signal: Option<&mut tokio::sync::watch::Receiver<bool>>,
+) -> Result<Option<Vec<u8>>> {
+    let response = send(transport, retrier, url, cancel_signal).await?;
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+
+    Ok(Some(response.bytes().await?.to_vec()))
+}
+
+/// Parse the bundled keyring once
+///
+/// An empty or unreadable keyring is an error, nothing could ever be verified with it.
+fn release_keys() -> Result<&'static [SignedPublicKey]> {
+    static KEYS: OnceLock<Result<Vec<SignedPublicKey>, String>> = OnceLock::new();
+
+    KEYS.get_or_init(|| parse_keys(RELEASE_KEYS).map_err(|err| err.to_string()))
+        .as_deref()
+        .map_err(|err| anyhow!("{err}, refresh it with `pnpm release-keys`"))
+}
+
+/// Parse an armored keyring, every key of it must be valid
+fn parse_keys(armored: &str) -> Result<Vec<SignedPublicKey>> {
+    if armored.trim().is_empty() {
+        return Err(anyhow!("the Node.js release keyring is empty"));
+    }
+
+    let (keys, _) = SignedPublicKey::from_armor_many(Cursor::new(armored))
+        .map_err(|err| anyhow!("the Node.js release keyring is invalid: {err}"))?;
+    let keys = keys
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| anyhow!("the Node.js release keyring is invalid: {err}"))?;
+    if keys.is_empty() {
+        return Err(anyhow!("the Node.js release keyring has no keys"));
+    }
+
+    Ok(keys)
+}
+
+/// A signature together with what it signs
+enum Signed<'a> {
+    /// binary detached signature (`SHASUMS256.txt.sig`)
+    Detached(StandaloneSignature, &'a [u8]),
+
+    /// clearsigned message (`SHASUMS256.txt.asc`)
+    Cleartext(CleartextSignedMessage),
+}
+
+impl Signed<'_> {
+    fn verify_with(&self, key: &impl PublicKeyTrait) -> bool {
+        match self {
+            Signed::Detached(signature, content) => signature.verify(key, content).is_ok(),
+            Signed::Cleartext(message) => message.verify(key).is_ok(),
+        }
+    }
+
+    /// Find the key of `keys` that made this signature,
+    /// subkeys are tried as well since some releasers sign with them
+    fn signer(&self, keys: &[SignedPublicKey]) -> Result<String> {
+        keys.iter()
+            .find(|key| {
+                self.verify_with(*key) || key.public_subkeys.iter().any(|sub| self.verify_with(sub))
+            })
+            .map(|key| {
+                key.fingerprint()
+                    .iter()
+                    .map(|byte| format!("{byte:02X}"))
+                    .collect()
+            })
+            .ok_or_else(|| anyhow!("{SHASUMS_FILENAME} is not signed by a Node.js release key"))
+    }
+}
+
+fn verify_detached(keys: &[SignedPublicKey], sig: &[u8], content: &[u8]) -> Result<String> {
+    let signature = StandaloneSignature::from_bytes(Cursor::new(sig))?;
+    Signed::Detached(signature, content).signer(keys)
+}
+
+/// The clearsigned message must sign the same content as the downloaded `SHASUMS256.txt`
+fn verify_cleartext(keys: &[SignedPublicKey], asc: &[u8], shasums: &str) -> Result<String> {
+    let (message, _) = CleartextSignedMessage::from_armor(Cursor::new(asc))?;
+    // the signed text is normalized to CRLF line endings
+    let signed = message.signed_text().replace("\r\n", "\n");
+    if signed.trim_end() != shasums.replace("\r\n", "\n").trim_end() {
+        return Err(anyhow!(
+            "the signed content does not match {SHASUMS_FILENAME}"
+        ));
+    }
+
+    Signed::Cleartext(message).signer(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// generated with gpg for these tests only, see `keys/fixtures`
+    const TEST_KEY: &str = include_str!("../../keys/fixtures/release-key.asc");
+    const TEST_FINGERPRINT: &str = "9F5B386E57FDAE777939F44148383BCDC127CF89";
+    const SHASUMS: &str = include_str!("../../keys/fixtures/SHASUMS256.txt");
+
+    #[test]
+    fn test_satisfies() {
+        let verified = SignatureStatus::Verified {
+            fingerprint: TEST_FINGERPRINT.into(),
+        };
+        let unverified = SignatureStatus::Unverified {
+            reason: "no signature".into(),
+        };
+        assert!(verified.satisfies(SignatureMode::Require));
+        assert!(!unverified.satisfies(SignatureMode::Require));
+        assert!(!SignatureStatus::Skipped.satisfies(SignatureMode::Require));
+        assert!(unverified.satisfies(SignatureMode::Warn));
+        assert!(SignatureStatus::Skipped.satisfies(SignatureMode::Skip));
+    }
+
+    /// A mirror whose signature files can not be downloaded
+    struct Unreachable;
+
+    impl Transport for Unreachable {
+        fn send(&self, _: reqwest::Request) -> crate::transport::Sending<'_> {
+            Box::pin(async { Err(anyhow!("connection reset")) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_shasums_unreachable() {
+        let retry = crate::net::Retry {
+            retries: 0,
+            ..Default::default()
+        };
+        let retrier = Retrier::new(Some(retry), None);
+        let verify = |mode| {
+            verify_shasums(
+                &Unreachable,
+                &retrier,
+                "https://mock.invalid/node",
+                "20.11.1",
+                SHASUMS,
+                mode,
+                None,
+            )
+        };
+
+        // the install goes on unverified, unless the signature is required
+        let status = verify(SignatureMode::Warn).await.unwrap();
+        assert!(
+            matches!(status, SignatureStatus::Unverified { reason } if reason.contains("connection reset"))
+        );
+        assert!(verify(SignatureMode::Require).await.is_err());
+        assert_eq!(
+            verify(SignatureMode::Skip).await.unwrap(),
+            SignatureStatus::Skipped
+        );
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert!(parse_keys("").is_err());
+        assert!(parse_keys("not a keyring").is_err());
+        assert_eq!(parse_keys(TEST_KEY).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_verify_detached() {
+        let keys = parse_keys(TEST_KEY).unwrap();
+        let sig = include_bytes!("../../keys/fixtures/SHASUMS256.txt.sig");
+        assert_eq!(
+            verify_detached(&keys, sig, SHASUMS.as_bytes()).unwrap(),
+            TEST_FINGERPRINT
+        );
+
+        // tampered
+        let tampered = SHASUMS.replacen('3', "4", 1);
+        assert!(verify_detached(&keys, sig, tampered.as_bytes()).is_err());
+
+        // signed by a key that is not in the keyring
+        let unknown = include_bytes!("../../keys/fixtures/unknown.sig");
+        assert!(verify_detached(&keys, unknown, SHASUMS.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_verify_cleartext() {
+        let keys = parse_keys(TEST_KEY).unwrap();
+        let asc = include_str!("../../keys/fixtures/SHASUMS256.txt.asc");
+        assert_eq!(
+            verify_cleartext(&keys, asc.as_bytes(), SHASUMS).unwrap(),
+            TEST_FINGERPRINT
+        );
+
+        // the signed text was tampered with, along with the downloaded file or not
+        let tampered = asc.replacen("node-v20", "node-v21", 1);
+        let tampered_shasums = SHASUMS.replacen("node-v20", "node-v21", 1);
+        assert!(verify_cleartext(&keys, tampered.as_bytes(), &tampered_shasums).is_err());
+        assert!(verify_cleartext(&keys, tampered.as_bytes(), SHASUMS).is_err());
+
+        // signed by a key that is not in the keyring
+        let unknown = include_str!("../../keys/fixtures/unknown.asc");
+        assert!(verify_cleartext(&keys, unknown.as_bytes(), SHASUMS).is_err());
+    }
+}
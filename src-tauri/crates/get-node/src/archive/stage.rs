@@ -0,0 +1,197 @@
+use anyhow::{bail, Context, Result};
+use node_semver::Version;
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+use tokio::{
+    fs::{create_dir_all, remove_dir_all, rename},
+    process::Command,
+};
+
+use crate::{
+    platform::{Os, Platform},
+    Error,
+};
+
+/// The prefix of the staging directories inside `dest`
+/// a leftover one is from an install that was interrupted
+pub const STAGING_PREFIX: &str = ".staging-";
+
+/// How long `node --version` may take when validating an install
+const VALIDATE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A private directory the archive is unpacked into,
+/// moved into place in one step once the install is validated
+///
+/// It is removed when dropped without being committed,
+/// so that a failed or cancelled install leaves nothing behind.
+pub(crate) struct Stage {
+    /// the staging directory, next to `target` so that the final rename is atomic
+    dir: PathBuf,
+
+    /// the version directory, e.g. `<dest>/20.11.1`
+    target: PathBuf,
+
+    version: String,
+
+    committed: bool,
+}
+
+impl Stage {
+    /// Create an empty staging directory for `version`
+    /// fails with `Error::AlreadyInstalled` if it is installed in `dest` already
+    pub(crate) async fn new(dest: &Path, version: &str) -> Result<Stage> {
+        let target = dest.join(version);
+        check_not_installed(&target, version)?;
+
+        let dir = dest.join(format!("{}{}", STAGING_PREFIX, version));
+        // left by an interrupted install
+        if dir.exists() {
+            remove_dir_all(&dir).await?;
+        }
+        create_dir_all(&dir)
+            .await
+            .with_context(|| format!("Failed to create the staging directory: {:?}", dir))?;
+
+        Ok(Stage {
+            dir,
+            target,
+            version: version.to_string(),
+            committed: false,
+        })
+    }
+
+    /// The directory to unpack the archive into
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Empty the staging directory, e.g. before retrying with another mirror
+    pub(crate) async fn reset(&self) -> Result<()> {
+        if self.dir.exists() {
+            remove_dir_all(&self.dir).await?;
+        }
+        create_dir_all(&self.dir).await?;
+        Ok(())
+    }
+
+    /// Validate the unpacked `name` directory and move it into place
+    /// return the path of the installed version
+    pub(crate) async fn commit(mut self, name: &str, platform: &Platform) -> Result<PathBuf> {
+        let root = self.dir.join(name);
+        validate(&root, &self.version, platform).await?;
+
+        // installed by another process in the meantime
+        check_not_installed(&self.target, &self.version)?;
+        rename(&root, &self.target)
+            .await
+            .with_context(|| format!("Failed to move the install into {:?}", self.target))?;
+        self.committed = true;
+
+        // only the empty staging directory is left
+        let _ = remove_dir_all(&self.dir).await;
+        Ok(self.target.clone())
+    }
+}
+
+impl Drop for Stage {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+fn check_not_installed(target: &Path, version: &str) -> Result<()> {
+    if target.exists() {
+        return Err(Error::AlreadyInstalled {
+            version: version.to_string(),
+            path: target.to_string_lossy().to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Check that the node binary exists in `root`,
+/// and that it runs and reports `version` when it is built for this host
+pub(crate) async fn validate(root: &Path, version: &str, platform: &Platform) -> Result<()> {
+    let binary = match platform.os {
+        Os::Windows => root.join("node.exe"),
+        _ => root.join("bin").join("node"),
+    };
+    if !binary.is_file() {
+        bail!("Invalid node archive, {:?} not found", binary);
+    }
+    if !runs_here(platform, version) {
+        return Ok(());
+    }
+
+    let output = Command::new(&binary)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(VALIDATE_TIMEOUT, output)
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out running {:?}", binary))?
+        .with_context(|| format!("Failed to run {:?}", binary))?;
+
+    let reported = String::from_utf8_lossy(&output.stdout);
+    let reported = reported.trim();
+    if !output.status.success() || reported != format!("v{}", version) {
+        bail!(
+            "Invalid node install, {:?} reports \"{}\" instead of \"v{}\"",
+            binary,
+            reported,
+            version
+        );
+    }
+    Ok(())
+}
+
+/// Whether the binary of the install can be run on this host
+fn runs_here(platform: &Platform, version: &str) -> bool {
+    let current = Platform::current();
+    let Ok(version) = Version::parse(version) else {
+        return false;
+    };
+    platform.os == current.os && platform.archive_arch(&version) == current.archive_arch(&version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stage_rollback() {
+        let dest = std::env::temp_dir().join("get-node-test-stage-rollback");
+        let _ = remove_dir_all(&dest).await;
+
+        let stage = Stage::new(&dest, "20.2.3").await.unwrap();
+        let dir = stage.dir().to_path_buf();
+        create_dir_all(dir.join("node-v20.2.3-linux-x64"))
+            .await
+            .unwrap();
+
+        // no node binary, nothing is installed & the staging directory is removed
+        let platform = Platform::new(Os::Linux, "x64");
+        assert!(stage
+            .commit("node-v20.2.3-linux-x64", &platform)
+            .await
+            .is_err());
+        assert!(!dir.exists());
+        assert!(!dest.join("20.2.3").exists());
+
+        create_dir_all(dest.join("20.2.3")).await.unwrap();
+        let err = Stage::new(&dest, "20.2.3").await.err().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::AlreadyInstalled { .. })
+        ));
+
+        remove_dir_all(&dest).await.unwrap();
+    }
+}
@@ -1,53 +1,259 @@
-use anyhow::{bail, Result};
-use async_compression::tokio::bufread::GzipDecoder;
+use anyhow::{anyhow, bail, Context as _, Result};
+use async_compression::tokio::bufread::{GzipDecoder, XzDecoder};
+use bytes::Bytes;
 use futures_util::StreamExt;
-use node_semver::Version;
-use std::{path::PathBuf, time::Duration};
+use reqwest::header::{HeaderValue, IF_RANGE, RANGE};
+use sha2::{Digest, Sha256};
+use std::{
+    path::Path,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
 use tokio::{
-    fs::{remove_dir_all, remove_file, rename, File},
-    io::{AsyncWriteExt, BufReader},
+    fs::{remove_file, rename, File},
+    io::{AsyncBufRead, AsyncRead, AsyncWriteExt, BufReader, ReadBuf},
+    sync::{mpsc, watch},
 };
 use tokio_tar::Archive;
+use tokio_util::io::StreamReader;
+
+use super::{
+    cache, cached, checksum,
+    download::{if_range, resumes, with_suffix},
+    download_archive, is_cancelled, mirror, platform, resolve, send, sources,
+    stage::Stage,
+    Failures, FetchConfig, FetchResult, OnProgress, Remote, Source, PARTIAL_SUFFIX,
+};
+use crate::{
+    net::{self, RateLimit, Retrier},
+    progress::{Meter, Progress},
+    transport::{self, Transport},
+};
 
-use super::{create_client, node::*, send, FetchConfig};
+pub async fn fetch(mut config: FetchConfig) -> Result<FetchResult> {
+    // fails early when the version is installed already, before downloading anything
+    let stage = Stage::new(Path::new(&config.dest), &config.version).await?;
+    let platform = platform(&config);
 
-pub async fn fetch(config: FetchConfig) -> Result<String> {
+    let archive = match cached(&config).await? {
+        Some(archive) => archive,
+        None if config.streaming.unwrap_or(false) => {
+            return fetch_streaming(&mut config, stage).await
+        }
+        None => download_archive(&mut config).await?,
+    };
     let FetchConfig {
-        dest,
-        mirror,
-        arch,
-        version,
-        proxy,
-        no_proxy,
-        timeout,
         mut cancel_signal,
         on_progress,
+        ..
     } = config;
 
-    let (name, full_name) = Node::archive_filename(&Version::parse(&version)?, arch);
-    let url = format!("{}/v{}/{}", mirror, &version, &full_name);
-    // timeout default value is `20s`
-    let timeout = timeout.unwrap_or(Duration::from_millis(20000));
-    let client = create_client(proxy, no_proxy, timeout)?;
+    // Create a buffered reader for the compressed data
+    let file = File::open(&archive.path).await?;
+    let total_size = file.metadata().await?.len();
+    let reader = BufReader::new(file);
+    let xz = archive.path.to_string_lossy().ends_with(".xz");
+
+    let installed = match unpack(
+        reader,
+        xz,
+        stage.dir(),
+        Some(total_size),
+        cancel_signal.as_mut(),
+        &on_progress,
+    )
+    .await
+    {
+        Ok(true) => {
+            on_progress(&Progress::Finalizing);
+            stage.commit(&archive.name, &platform).await
+        }
+        Ok(false) => Err(anyhow!("Unzipping was cancelled")),
+        Err(err) => Err(err),
+    };
+    // the archive is not kept unless cached, whether it was installed or not
+    let _ = archive.cleanup().await;
+    let path = installed?;
+
+    Ok(FetchResult {
+        path: path.to_string_lossy().to_string(),
+        signature: archive.signature,
+        mirror: archive.mirror,
+    })
+}
 
-    let response = send(&client, &url, cancel_signal.as_mut()).await?;
+/// Stream the tarball from the first mirror that serves it into `stage`
+async fn fetch_streaming(config: &mut FetchConfig, stage: Stage) -> Result<FetchResult> {
+    let platform = platform(config);
+    let mut failures = Failures::default();
+    for source in sources(config)? {
+        // the unofficial builds only stand in for a missing official archive
+        if source.fallback && !failures.all_not_found() {
+            break;
+        }
+
+        let base = source.mirror.download_base();
+        match stream_from(config, &source, stage.dir()).await {
+            Ok(remote) => {
+                mirror::report_success(base);
+                (config.on_progress)(&Progress::Finalizing);
+                let path = stage.commit(&remote.name, &platform).await?;
+                return Ok(FetchResult {
+                    path: path.to_string_lossy().to_string(),
+                    signature: remote.signature,
+                    mirror: remote.mirror,
+                });
+            }
+            Err(err) if is_cancelled(config) => return Err(err),
+            Err(err) => {
+                failures.push(base, err);
+                // nothing unpacked from a failed mirror is kept
+                stage.reset().await?;
+            }
+        }
+    }
+
+    Err(failures.into_error())
+}
+
+/// Download and unpack the tarball at the same time, without a temp file
+///
+/// The response body is tee'd to the hasher (and to the cache when enabled),
+/// the tarball is unpacked into `dir` and must be discarded if this fails.
+async fn stream_from(config: &mut FetchConfig, source: &Source, dir: &Path) -> Result<Remote> {
+    let remote = resolve(config, source).await?;
+    let FetchConfig {
+        version,
+        cache_dir,
+        retry,
+        rate_limit,
+        cancel_signal,
+        on_progress,
+        ..
+    } = config;
+    let on_progress: &OnProgress = on_progress;
+    let cache_path = match cache_dir.as_deref() {
+        Some(cache_dir) => {
+            Some(cache::prepare(Path::new(cache_dir), &remote.mirror, &remote.full_name).await?)
+        }
+        None => None,
+    };
+    let part_path = cache_path
+        .as_deref()
+        .map(|path| with_suffix(path, PARTIAL_SUFFIX));
+
+    let retrier = Retrier::new(*retry, Some(on_progress));
+    on_progress(&Progress::Connecting {
+        url: remote.url.clone(),
+    });
+    let response = send(
+        &*remote.transport,
+        &retrier,
+        &remote.url,
+        cancel_signal.as_mut(),
+    )
+    .await?;
 
     let status = response.status();
     if !status.is_success() {
         bail!(format!("HTTP failure ({status})"));
     }
 
-    let total_size = response
-        .content_length()
-        .ok_or_else(|| anyhow::anyhow!("Failed to get content length"))?;
+    // a small buffer is enough, it only applies back pressure on the download
+    let (sender, receiver) = mpsc::channel(16);
+    let chunks = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+    let reader = StreamReader::new(Box::pin(chunks));
+    let xz = remote.full_name.ends_with(".xz");
+    // unknown with a chunked response
+    let total_size = transport::content_length(&response);
+
+    let ret = tokio::try_join!(
+        pump(
+            &*remote.transport,
+            &remote.url,
+            &retrier,
+            response,
+            sender,
+            part_path.as_deref(),
+            rate_limit.as_ref(),
+            cancel_signal.clone(),
+            on_progress
+        ),
+        unpack(
+            reader,
+            xz,
+            dir,
+            total_size,
+            cancel_signal.as_mut(),
+            on_progress
+        ),
+    )
+    .and_then(|(actual, unpacked)| {
+        if !unpacked {
+            bail!("Unzipping was cancelled");
+        }
+        on_progress(&Progress::Verifying);
+        checksum::verify(&remote.full_name, &remote.expected, &actual)
+    });
+
+    if let Err(err) = ret {
+        if let Some(part_path) = &part_path {
+            let _ = remove_file(part_path).await;
+        }
+        return Err(err);
+    }
+
+    if let (Some(cache_path), Some(part_path)) = (&cache_path, &part_path) {
+        rename(part_path, cache_path).await?;
+        cache::store(
+            cache_path,
+            &remote.mirror,
+            version,
+            &remote.expected,
+            &remote.signature,
+        )
+        .await?;
+    }
+
+    Ok(remote)
+}
+
+/// Feed the response body to the unpacker through `sender` and return its sha256
+///
+/// Every byte is hashed (and written to `cache_path` if any),
+/// even after the unpacker stopped reading the padding at the end of the archive.
+/// A body broken off midway (reset connection, read timeout) is requested again
+/// from where it stopped with `Range` / `If-Range`, the unpacker can not start over.
+#[allow(clippy::too_many_arguments)]
+async fn pump(
+    transport: &dyn Transport,
+    url: &str,
+    retrier: &Retrier<'_>,
+    response: reqwest::Response,
+    sender: mpsc::Sender<std::io::Result<Bytes>>,
+    cache_path: Option<&Path>,
+    rate_limit: Option<&RateLimit>,
+    mut cancel_signal: Option<watch::Receiver<bool>>,
+    on_progress: &OnProgress,
+) -> Result<String> {
+    // unknown with a chunked response
+    let meter = Meter::new(0, transport::content_length(&response));
+    let validator = if_range(&response);
     let mut downloaded_size = 0;
-    let dest = PathBuf::from(dest);
-    let temp_file_path = dest.join(&full_name);
+    let mut attempt = 0;
+    let mut hasher = Sha256::new();
+    let mut file = match cache_path {
+        Some(path) => Some(File::create(path).await?),
+        None => None,
+    };
 
-    // start to download file
-    let mut temp_file = File::create(&temp_file_path).await?;
     let mut stream = response.bytes_stream();
-    // write stream buffer to file
     while let Some(chunk) = match cancel_signal.as_mut() {
         Some(cancel_receiver) => {
             tokio::select! {
@@ -55,64 +261,327 @@ pub async fn fetch(config: FetchConfig) -> Result<String> {
                     chunk
                 },
                 _ = cancel_receiver.changed() => {
-                    let _ = remove_file(temp_file_path).await;
                     bail!("Download was cancelled");
                 }
             }
         }
         None => stream.next().await,
     } {
-        let chunk = chunk?;
-        downloaded_size += chunk.len();
-        temp_file.write_all(&chunk).await?;
-        on_progress("download", downloaded_size as usize, total_size as usize);
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                let err = err.into();
+                let resumable = validator.is_some()
+                    && attempt < retrier.retry.retries
+                    && net::is_transient_body_error(&err);
+                if !resumable {
+                    return Err(err);
+                }
+                attempt += 1;
+                let delay = retrier.retry.delay(attempt);
+                retrier
+                    .backoff(attempt, delay, cancel_signal.as_mut())
+                    .await?;
+                let response = resume(
+                    transport,
+                    url,
+                    retrier,
+                    downloaded_size,
+                    validator.as_deref().unwrap_or_default(),
+                    cancel_signal.as_mut(),
+                )
+                .await
+                .with_context(|| format!("Failed to resume the download after: {err}"))?;
+                stream = response.bytes_stream();
+                continue;
+            }
+        };
+        net::throttle(rate_limit, chunk.len(), cancel_signal.as_mut()).await?;
+        downloaded_size += chunk.len() as u64;
+        hasher.update(&chunk);
+        if let Some(file) = file.as_mut() {
+            file.write_all(&chunk).await?;
+        }
+        on_progress(&Progress::Downloading(meter.transfer(downloaded_size)));
+        // the unpacker is gone once it is done, keep hashing anyway
+        let _ = sender.send(Ok(chunk)).await;
+    }
+    if let Some(file) = file {
+        file.sync_all().await?;
     }
-    temp_file.sync_all().await?;
-    drop(temp_file);
 
-    // Create a buffered reader for the compressed data
-    let file = File::open(&temp_file_path).await?;
-    let reader = BufReader::new(file);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Request the rest of the body at `url` from `offset`
+/// fails when the server does not honor the range or the file has changed
+async fn resume(
+    transport: &dyn Transport,
+    url: &str,
+    retrier: &Retrier<'_>,
+    offset: u64,
+    validator: &str,
+    cancel_signal: Option<&mut watch::Receiver<bool>>,
+) -> Result<reqwest::Response> {
+    let mut request = transport::get(url)?;
+    let headers = request.headers_mut();
+    headers.insert(RANGE, HeaderValue::from_str(&format!("bytes={offset}-"))?);
+    headers.insert(IF_RANGE, HeaderValue::from_str(validator)?);
+    let response = net::send(transport, request, retrier, cancel_signal).await?;
+    if !resumes(&response, offset) {
+        bail!(format!(
+            "HTTP failure ({}), the mirror can not resume the download",
+            response.status()
+        ));
+    }
+    Ok(response)
+}
+
+/// Unpack the tarball (`.tar.gz` or `.tar.xz`) at `path` into `dest`, e.g. a Linux archive on a Windows host
+///
+/// The entries escaping `dest` (`../`, absolute paths, writes through symlinks) are skipped or rejected.
+pub async fn untar(path: &Path, dest: &Path) -> Result<()> {
+    let reader = BufReader::new(File::open(path).await?);
+    let xz = path.to_string_lossy().ends_with(".xz");
+    unpack(reader, xz, dest, None, None, &|_: &Progress| {}).await?;
+    Ok(())
+}
 
-    // Initialize the GzipDecoder
-    let decoded = GzipDecoder::new(reader);
+/// Unpack the compressed tarball read from `reader` into `dest`
+/// return `false` when it was cancelled
+///
+/// The progress is the compressed bytes consumed against `total_size`, the archive size,
+/// the uncompressed size is unknown until the end of the archive.
+/// `total_size` is `None` when unknown, e.g. streaming a chunked response.
+async fn unpack<R>(
+    reader: R,
+    xz: bool,
+    dest: &Path,
+    total_size: Option<u64>,
+    mut cancel_signal: Option<&mut watch::Receiver<bool>>,
+    on_progress: &OnProgress,
+) -> Result<bool>
+where
+    R: AsyncBufRead + Unpin + Send + 'static,
+{
+    let consumed = Arc::new(AtomicUsize::new(0));
+    let meter = Meter::new(0, total_size);
+    let reader = Counting {
+        inner: reader,
+        consumed: consumed.clone(),
+    };
+
+    // Initialize the decoder according to the archive format (`tar.xz` or `tar.gz`)
+    let decoded: Box<dyn AsyncRead + Unpin + Send> = if xz {
+        Box::new(XzDecoder::new(reader))
+    } else {
+        Box::new(GzipDecoder::new(reader))
+    };
     // Initialize the tar archive with the decoded reader
     let mut tarball = Archive::new(decoded);
 
     // Unpack the tarball to the destination directory and report progress
-    let mut entries: tokio_tar::Entries<GzipDecoder<BufReader<File>>> = tarball.entries()?;
-    let mut unpacked_size = 0;
+    let mut entries = tarball.entries()?;
 
-    while let Some(entry) = match cancel_signal.as_mut() {
+    while let Some(entry) = match cancel_signal.as_deref_mut() {
         Some(cancel_receiver) => {
             tokio::select! {
                 entry = entries.next() => {
                     entry
                 },
                 _ = cancel_receiver.changed() => {
-                    let (r_download, r_unzip) = tokio::join!(remove_file(temp_file_path), remove_dir_all(dest.join(&name)));
-                    r_download?;
-                    r_unzip?;
-                    bail!("Unzipping was cancelled");
+                    return Ok(false);
                 }
             }
         }
         None => entries.next().await,
     } {
         let mut entry = entry?;
-        let entry_size = entry.header().size()?;
-        entry.unpack_in(&dest).await?;
-        unpacked_size += entry_size;
+        let is_symlink = entry.header().entry_type().is_symlink();
+        // the symlinks (e.g. `bin/npm`) are part of the install, it is incomplete without them
+        entry.unpack_in(dest).await.with_context(|| {
+            if is_symlink && cfg!(windows) {
+                "Failed to create a symlink, it needs the Developer Mode or administrator rights on Windows"
+            } else {
+                "Failed to unpack the tarball"
+            }
+        })?;
+
+        // the decoder reads ahead, it may be past the end of the entry
+        let unpacked = consumed.load(Ordering::Relaxed) as u64;
+        let unpacked = total_size.map_or(unpacked, |total_size| unpacked.min(total_size));
+        on_progress(&Progress::Extracting(meter.transfer(unpacked)));
+    }
+    let unpacked = total_size.unwrap_or(consumed.load(Ordering::Relaxed) as u64);
+    on_progress(&Progress::Extracting(meter.transfer(unpacked)));
+
+    Ok(true)
+}
+
+/// A reader counting the bytes consumed from `inner`
+struct Counting<R> {
+    inner: R,
+    consumed: Arc<AtomicUsize>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Counting<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let ret = Pin::new(&mut this.inner).poll_read(cx, buf);
+        this.consumed
+            .fetch_add(buf.filled().len() - filled, Ordering::Relaxed);
+        ret
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncBufRead for Counting<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<&[u8]>> {
+        Pin::new(&mut self.get_mut().inner).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        this.consumed.fetch_add(amt, Ordering::Relaxed);
+        Pin::new(&mut this.inner).consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_compression::tokio::write::GzipEncoder;
+    use std::sync::Mutex;
 
-        //todo Get the total size of the decompressed file on a Unix system
-        on_progress("unzip", unpacked_size as usize, unpacked_size as usize);
+    async fn tarball() -> Vec<u8> {
+        let mut builder = tokio_tar::Builder::new(GzipEncoder::new(Vec::new()));
+        for name in ["bin/node", "README.md"] {
+            let content = name.repeat(100);
+            let mut header = tokio_tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .await
+                .unwrap();
+        }
+        let mut encoder = builder.into_inner().await.unwrap();
+        encoder.shutdown().await.unwrap();
+        encoder.into_inner()
     }
 
-    let (_rename_future, _remove_future) = tokio::join!(
-        rename(dest.join(&name), dest.join(&version)),
-        remove_file(temp_file_path)
-    );
+    #[tokio::test]
+    async fn test_untar() {
+        let root = std::env::temp_dir().join(format!("get-node-untar-{}", std::process::id()));
+        let _ = tokio::fs::remove_dir_all(&root).await;
+        tokio::fs::create_dir_all(&root).await.unwrap();
+        let path = root.join("node-v20.11.1-linux-x64.tar.gz");
+        tokio::fs::write(&path, tarball().await).await.unwrap();
 
-    let path = dest.join(&version).to_string_lossy().to_string();
-    Ok(path)
+        let dest = root.join("dest");
+        untar(&path, &dest).await.unwrap();
+        assert_eq!(
+            tokio::fs::read_to_string(dest.join("bin").join("node"))
+                .await
+                .unwrap(),
+            "bin/node".repeat(100)
+        );
+        assert!(dest.join("README.md").is_file());
+
+        // not a tarball
+        tokio::fs::write(&path, "not a tarball").await.unwrap();
+        assert!(untar(&path, &root.join("invalid")).await.is_err());
+
+        let _ = tokio::fs::remove_dir_all(&root).await;
+    }
+
+    #[tokio::test]
+    async fn test_unpack_progress() {
+        let data = tarball().await;
+        for total_size in [Some(data.len() as u64), None] {
+            let dest = std::env::temp_dir().join(format!(
+                "get-node-unpack-{}-{}",
+                std::process::id(),
+                total_size.is_some()
+            ));
+            let _ = tokio::fs::remove_dir_all(&dest).await;
+            tokio::fs::create_dir_all(&dest).await.unwrap();
+
+            let events = Arc::new(Mutex::new(Vec::new()));
+            let on_progress = {
+                let events = events.clone();
+                move |progress: &Progress| {
+                    if let Progress::Extracting(transfer) = progress {
+                        events.lock().unwrap().push(*transfer);
+                    }
+                }
+            };
+            let reader = BufReader::new(std::io::Cursor::new(data.clone()));
+            let unpacked = unpack(reader, false, &dest, total_size, None, &on_progress)
+                .await
+                .unwrap();
+            assert!(unpacked);
+            assert!(dest.join("bin").join("node").is_file());
+
+            // reported as well when the archive size is unknown, e.g. a chunked response
+            let events = events.lock().unwrap().clone();
+            assert_eq!(events.len(), 3);
+            let last = events.last().unwrap();
+            assert_eq!(last.total, total_size);
+            match total_size {
+                Some(total_size) => assert_eq!(last.done, total_size),
+                // the unpacker stops before the end of the archive, e.g. the gzip trailer
+                None => assert!(last.done > 0 && last.done <= data.len() as u64),
+            }
+
+            let _ = tokio::fs::remove_dir_all(&dest).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pump_broken_body() {
+        use super::super::download::tests::{retrier, Flaky, URL};
+
+        let data = tarball().await;
+        let cut = data.len() / 2;
+        for retries in [0, 1] {
+            let flaky = Flaky::new(data.clone(), cut);
+            let response = flaky.send(transport::get(URL).unwrap()).await.unwrap();
+            let (sender, mut receiver) = mpsc::channel(16);
+            let ret = pump(
+                &*flaky,
+                URL,
+                &retrier(retries),
+                response,
+                sender,
+                None,
+                None,
+                None,
+                &|_: &Progress| {},
+            )
+            .await;
+
+            let mut fed = Vec::new();
+            while let Some(chunk) = receiver.recv().await {
+                fed.extend_from_slice(&chunk.unwrap());
+            }
+            if retries == 0 {
+                assert!(ret.is_err());
+                assert_eq!(fed.len(), cut);
+                continue;
+            }
+            // the unpacker is fed the rest of the body as if nothing happened
+            assert_eq!(ret.unwrap(), format!("{:x}", Sha256::digest(&data)));
+            assert_eq!(fed, data);
+            assert_eq!(
+                *flaky.ranges.lock().unwrap(),
+                vec![None, Some(format!("bytes={cut}-"))]
+            );
+        }
+    }
 }
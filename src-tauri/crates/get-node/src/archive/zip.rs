@@ -1,99 +1,104 @@
-use std::time::Duration;
-
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use async_zip::tokio::read::seek::ZipFileReader;
-use futures_util::StreamExt;
-use node_semver::Version;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
 use tokio::{
-    fs::{create_dir_all, remove_dir_all, remove_file, rename, File, OpenOptions},
-    io::{AsyncWriteExt, BufReader},
+    fs::{create_dir_all, File, OpenOptions},
+    io::BufReader,
+    sync::watch,
 };
 use tokio_util::compat::TokioAsyncWriteCompatExt;
 
-use super::{create_client, node::*, send, FetchConfig, PathBuf};
+use super::{obtain, platform, stage::Stage, FetchConfig, FetchResult, OnProgress};
+use crate::progress::{Meter, Progress};
+
+pub async fn fetch(mut config: FetchConfig) -> Result<FetchResult> {
+    // fails early when the version is installed already, before downloading anything
+    let stage = Stage::new(Path::new(&config.dest), &config.version).await?;
+    let platform = platform(&config);
 
-pub async fn fetch(config: FetchConfig) -> Result<String> {
+    let archive = obtain(&mut config).await?;
     let FetchConfig {
-        dest,
-        mirror,
-        arch,
-        version,
-        proxy,
-        no_proxy,
-        timeout,
         mut cancel_signal,
         on_progress,
+        ..
     } = config;
 
-    let (name, full_name) = Node::archive_filename(&Version::parse(&version)?, arch);
-    let url = format!("{}/v{}/{}", mirror, &version, &full_name);
-    // timeout default value is `20s`
-    let timeout = timeout.unwrap_or(Duration::from_millis(20000));
-    let client = create_client(proxy, no_proxy, timeout)?;
-
-    let response = send(&client, &url, cancel_signal.as_mut()).await?;
-
-    let status = response.status();
-    if !status.is_success() {
-        bail!(format!("HTTP failure ({status})"));
-    }
-
-    let total_size = response
-        .content_length()
-        .ok_or_else(|| anyhow::anyhow!("Failed to get content length"))?;
-    let mut downloaded_size = 0;
-    let dest = PathBuf::from(dest);
-    let temp_file_path = dest.join(&full_name);
-    let mut temp_file = File::create(&temp_file_path).await?;
-    let mut stream = response.bytes_stream();
-
-    while let Some(chunk) = match cancel_signal.as_mut() {
-        Some(cancel_receiver) => {
-            tokio::select! {
-                chunk = stream.next() => {
-                    chunk
-                },
-                _ = cancel_receiver.changed() => {
-                    let _ = remove_file(temp_file_path).await;
-                    bail!("Download was cancelled");
-                }
-            }
+    let installed = match unpack(
+        &archive.path,
+        stage.dir(),
+        cancel_signal.as_mut(),
+        &on_progress,
+    )
+    .await
+    {
+        Ok(true) => {
+            on_progress(&Progress::Finalizing);
+            stage.commit(&archive.name, &platform).await
         }
-        None => stream.next().await,
-    } {
-        let chunk = chunk?;
-        downloaded_size += chunk.len();
-        temp_file.write_all(&chunk).await?;
-        (on_progress)("download", downloaded_size as usize, total_size as usize);
-    }
+        Ok(false) => Err(anyhow!("Unzipping was cancelled")),
+        Err(err) => Err(err),
+    };
+    // the archive is not kept unless cached, whether it was installed or not
+    let _ = archive.cleanup().await;
+    let path = installed?;
+
+    Ok(FetchResult {
+        path: path.to_string_lossy().to_string(),
+        signature: archive.signature,
+        mirror: archive.mirror,
+    })
+}
 
-    temp_file.sync_all().await?;
-    drop(temp_file);
+/// Unpack the zip archive at `path` into `dest`, e.g. a Windows archive on a Linux host
+///
+/// The entries escaping `dest` (`../`, absolute paths, symlinks pointing outside) are rejected,
+/// the Unix permissions of the entries are applied on Unix hosts.
+pub async fn unzip(path: &Path, dest: &Path) -> Result<()> {
+    unpack(path, dest, None, &|_: &Progress| {}).await?;
+    Ok(())
+}
 
+/// Unpack the zip archive at `path` into `dest`
+/// return `false` when it was cancelled
+async fn unpack(
+    path: &Path,
+    dest: &Path,
+    mut cancel_signal: Option<&mut watch::Receiver<bool>>,
+    on_progress: &OnProgress,
+) -> Result<bool> {
     // Create a buffered reader for the compressed data
-    let file = File::open(&temp_file_path).await?;
+    let file = File::open(path).await?;
     let mut reader = BufReader::new(file);
 
-    // Initialize the GzipDecoder
     let mut zip = ZipFileReader::with_tokio(&mut reader).await?;
-    // Unpack the tarball to the destination directory and report progress
+    // Unpack the archive to the destination directory and report progress
     let total_entries = zip.file().entries().len();
+    let meter = Meter::new(0, Some(total_entries as u64));
+    // the symlinks unpacked so far, relative to `dest`
+    let mut links = HashSet::new();
 
-    let mut is_cancel = false;
     for index in 0..total_entries {
         // Check for cancel signal
         if let Some(cancel_receiver) = cancel_signal.as_mut() {
             if *cancel_receiver.borrow() {
-                is_cancel = true;
-                break;
+                return Ok(false);
             }
         }
 
         let entry = zip.file().entries().get(index).unwrap();
-        let path = dest.join(entry.filename().as_str()?);
+        let name = entry.filename().as_str()?.to_string();
+        let relative = entry_path(&name)?;
+        // the links are checked by their text, one already on disk could lead anywhere
+        check_parents(&relative, &links)
+            .with_context(|| format!("Invalid zip entry \"{}\"", name))?;
+        let path = dest.join(&relative);
         // If the filename of the entry ends with '/', it is treated as a directory.
         // This is implemented by previous versions of this crate and the Python Standard Library.
         let entry_is_dir = entry.dir()?;
+        let mode = entry.unix_permissions().map(u32::from);
         let mut entry_reader = zip.reader_without_entry(index).await?;
 
         if entry_is_dir {
@@ -101,39 +106,373 @@ pub async fn fetch(config: FetchConfig) -> Result<String> {
             if !path.exists() {
                 create_dir_all(&path).await?;
             }
+            on_progress(&Progress::Extracting(meter.transfer(index as u64 + 1)));
+            continue;
+        }
+
+        // Creates parent directories. They may not exist if iteration is out of order
+        // or the archive does not contain directory entries.
+        let parent = path.parent().unwrap();
+        if !parent.is_dir() {
+            create_dir_all(parent).await?;
+        }
+
+        if mode.is_some_and(|mode| mode & S_IFMT == S_IFLNK) {
+            // the content of a symlink entry is its target
+            let mut target = String::new();
+            futures_lite::io::AsyncReadExt::read_to_string(&mut entry_reader, &mut target).await?;
+            check_link(&relative, &target, &links)
+                .with_context(|| format!("Invalid symlink \"{}\" -> \"{}\"", name, target))?;
+            symlink(&target, &path).await?;
+            links.insert(relative);
         } else {
-            // Creates parent directories. They may not exist if iteration is out of order
-            // or the archive does not contain directory entries.
-            let parent = path.parent().unwrap();
-            if !parent.is_dir() {
-                create_dir_all(parent).await?;
-            }
+            // `create_new` never follows an existing file or symlink
             let writer = OpenOptions::new()
                 .write(true)
                 .create_new(true)
                 .open(&path)
                 .await?;
             futures_lite::io::copy(&mut entry_reader, &mut writer.compat_write()).await?;
+            if let Some(mode) = mode {
+                set_mode(&path, mode).await?;
+            }
+        }
+
+        on_progress(&Progress::Extracting(meter.transfer(index as u64 + 1)));
+    }
+
+    Ok(true)
+}
+
+/// The file type bits of a Unix mode
+const S_IFMT: u32 = 0o170000;
+
+/// The file type of a symlink
+const S_IFLNK: u32 = 0o120000;
+
+/// The path of an entry relative to the destination
+/// rejected when it is absolute or escapes it with `..`
+fn entry_path(name: &str) -> Result<PathBuf> {
+    // zip entries use `/`, but some Windows tools write `\`
+    let name = name.replace('\\', "/");
+    if name.starts_with('/') || name.as_bytes().get(1) == Some(&b':') {
+        bail!("The zip entry \"{}\" has an absolute path", name);
+    }
+
+    let mut path = PathBuf::new();
+    for part in name.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => bail!("The zip entry \"{}\" escapes the destination", name),
+            part => path.push(part),
         }
+    }
+    if path.as_os_str().is_empty() {
+        bail!("The zip entry \"{}\" has an empty path", name);
+    }
+    Ok(path)
+}
 
-        on_progress("unzip", index + 1, total_entries);
+/// Check that the symlink at `link` (relative to the destination) pointing to `target`
+/// stays inside the destination
+///
+/// The target is resolved by its text, so it must not go through one of the symlinks
+/// unpacked before, e.g. `a -> p/q/x/../..` after `p/q/x -> ..`
+fn check_link(link: &Path, target: &str, links: &HashSet<PathBuf>) -> Result<()> {
+    let target = target.replace('\\', "/");
+    if target.is_empty() || target.starts_with('/') || target.as_bytes().get(1) == Some(&b':') {
+        bail!("the target must be a relative path");
+    }
+
+    let mut resolved = link.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut parts = target
+        .split('/')
+        .filter(|part| !matches!(*part, "" | "."))
+        .peekable();
+    while let Some(part) = parts.next() {
+        if part == ".." {
+            if !resolved.pop() {
+                bail!("the target is outside the destination");
+            }
+            continue;
+        }
+        resolved.push(part);
+        // the last part may be a symlink, it is checked to stay inside already
+        if parts.peek().is_some() && links.contains(&resolved) {
+            bail!(
+                "the target goes through the symlink \"{}\"",
+                resolved.display()
+            );
+        }
     }
+    Ok(())
+}
 
-    if is_cancel {
-        let (r_download, r_unzip) = tokio::join!(
-            remove_file(temp_file_path),
-            remove_dir_all(dest.join(&name))
+/// Check that no parent of the entry at `path` (relative to the destination)
+/// is one of the symlinks unpacked before, e.g. `a/b/c` after `a/b -> ..`
+fn check_parents(path: &Path, links: &HashSet<PathBuf>) -> Result<()> {
+    if path
+        .ancestors()
+        .skip(1)
+        .any(|parent| links.contains(parent))
+    {
+        bail!("a parent directory is a symlink");
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn symlink(target: &str, path: &Path) -> Result<()> {
+    tokio::fs::symlink(target, path).await?;
+    Ok(())
+}
+
+/// Creating symlinks needs extra privileges on Windows,
+/// the Windows archives have none anyway
+#[cfg(not(unix))]
+async fn symlink(_target: &str, _path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Apply the permission bits of a Unix mode, without setuid, setgid & sticky
+#[cfg(unix)]
+async fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = mode & 0o777;
+    // a mode of `0` means the archiver stored none
+    if mode != 0 {
+        tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).await?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn set_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_zip::{tokio::write::ZipFileWriter, Compression, ZipEntryBuilder};
+
+    #[test]
+    fn test_entry_path() {
+        assert_eq!(
+            entry_path("node-v20.11.1-win-x64/node.exe").unwrap(),
+            PathBuf::from("node-v20.11.1-win-x64").join("node.exe")
+        );
+        assert_eq!(
+            entry_path("./node\\npm.cmd").unwrap(),
+            PathBuf::from("node").join("npm.cmd")
         );
-        r_download?;
-        r_unzip?;
-        bail!("Unzipping was cancelled");
+        assert!(entry_path("../evil.exe").is_err());
+        assert!(entry_path("node/../../evil.exe").is_err());
+        assert!(entry_path("/etc/passwd").is_err());
+        assert!(entry_path("C:/Windows/evil.exe").is_err());
+        assert!(entry_path("./").is_err());
     }
 
-    let (_rename_future, _remove_future) = tokio::join!(
-        rename(dest.join(&name), dest.join(&version)),
-        remove_file(temp_file_path)
-    );
+    #[test]
+    fn test_check_link() {
+        let link = Path::new("node/bin/npm");
+        let links = HashSet::new();
+        assert!(check_link(link, "../lib/node_modules/npm/bin/npm-cli.js", &links).is_ok());
+        assert!(check_link(link, "../../../etc/passwd", &links).is_err());
+        assert!(check_link(link, "/etc/passwd", &links).is_err());
 
-    let path = dest.join(&version).to_string_lossy().to_string();
-    Ok(path)
+        // `p/q/x/../..` looks like `p`, but `p/q/x` points at `p` already
+        let links = HashSet::from([PathBuf::from("p/q/x")]);
+        assert!(check_link(Path::new("p/q/x"), "..", &HashSet::new()).is_ok());
+        assert!(check_link(Path::new("a"), "p/q/x/../..", &links).is_err());
+        assert!(check_link(Path::new("a"), "p/q/x", &links).is_ok());
+        assert!(check_link(Path::new("a"), "p/q/y/../..", &links).is_ok());
+    }
+
+    #[test]
+    fn test_check_parents() {
+        // `a/b/c -> ..` looks one level deep, but `a/b` points at the destination already
+        let links = HashSet::from([PathBuf::from("a/b")]);
+        assert!(check_link(Path::new("a/b/c"), "..", &HashSet::new()).is_ok());
+        assert!(check_parents(Path::new("a/b/c"), &links).is_err());
+        assert!(check_parents(Path::new("a/b/c/evil"), &links).is_err());
+        assert!(check_parents(Path::new("a/b"), &links).is_ok());
+        assert!(check_parents(Path::new("a/bc"), &links).is_ok());
+    }
+
+    async fn write_zip(path: &Path, entries: &[(&str, u16, &[u8])]) {
+        let mut writer = ZipFileWriter::with_tokio(File::create(path).await.unwrap());
+        for (name, mode, content) in entries {
+            let builder = ZipEntryBuilder::new((*name).to_string().into(), Compression::Stored)
+                .unix_permissions(*mode);
+            writer.write_entry_whole(builder, content).await.unwrap();
+        }
+        writer.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unzip() {
+        let root = std::env::temp_dir().join("get-node-test-unzip");
+        let _ = tokio::fs::remove_dir_all(&root).await;
+        create_dir_all(&root).await.unwrap();
+
+        let archive = root.join("node.zip");
+        write_zip(
+            &archive,
+            &[
+                ("node/bin/node", 0o100755, &b"#!/bin/sh"[..]),
+                ("node/README.md", 0o100644, &b"# node"[..]),
+                ("node/bin/readme", 0o120777, &b"../README.md"[..]),
+            ],
+        )
+        .await;
+        let dest = root.join("dest");
+        unzip(&archive, &dest).await.unwrap();
+        assert!(dest.join("node/README.md").is_file());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = std::fs::metadata(dest.join("node/bin/node"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o755);
+            assert_eq!(
+                std::fs::read_link(dest.join("node/bin/readme")).unwrap(),
+                PathBuf::from("../README.md")
+            );
+        }
+
+        // nothing is written outside of `dest`
+        let evil = root.join("evil.zip");
+        write_zip(&evil, &[("node/../../evil", 0o100644, &b"evil"[..])]).await;
+        assert!(unzip(&evil, &root.join("dest-evil")).await.is_err());
+        assert!(!root.join("evil").exists());
+
+        // chained symlinks, each one staying inside by its text
+        let chained = root.join("chained.zip");
+        write_zip(
+            &chained,
+            &[
+                ("a/b", 0o120777, &b".."[..]),
+                ("a/b/c", 0o120777, &b".."[..]),
+                ("a/b/c/evil", 0o100644, &b"evil"[..]),
+            ],
+        )
+        .await;
+        let dest = root.join("dest-chained");
+        assert!(unzip(&chained, &dest).await.is_err());
+        assert!(!root.join("evil").exists());
+        assert!(!dest.join("c").exists());
+
+        // a symlink target going through another symlink, `a` would point at `root`
+        let through = root.join("through.zip");
+        write_zip(
+            &through,
+            &[
+                ("p/q/x", 0o120777, &b".."[..]),
+                ("a", 0o120777, &b"p/q/x/../.."[..]),
+                ("a/evil", 0o100644, &b"evil"[..]),
+            ],
+        )
+        .await;
+        let dest = root.join("dest-through");
+        assert!(unzip(&through, &dest).await.is_err());
+        assert!(!dest.join("a").exists());
+        assert!(!root.join("evil").exists());
+
+        let _ = tokio::fs::remove_dir_all(&root).await;
+    }
+
+    // a Windows archive is not run on this host to validate the install
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_fetch_local_mirror() {
+        use crate::{
+            archive::{
+                fetch_native, sha256_file, SignatureMode, SignatureStatus, SHASUMS_FILENAME,
+            },
+            net::RateLimit,
+            platform::{Os, Platform},
+            progress,
+        };
+        use futures_util::StreamExt;
+
+        let root = std::env::temp_dir().join("get-node-test-local-mirror");
+        let _ = tokio::fs::remove_dir_all(&root).await;
+        let release = root.join("mirror").join("v20.11.1");
+        create_dir_all(&release).await.unwrap();
+
+        // a fixture mirror laid out like `https://nodejs.org/dist`
+        let archive = release.join("node-v20.11.1-win-x64.zip");
+        write_zip(
+            &archive,
+            &[("node-v20.11.1-win-x64/node.exe", 0o100755, &b"MZ"[..])],
+        )
+        .await;
+        let shasums = format!(
+            "{}  node-v20.11.1-win-x64.zip\n",
+            sha256_file(&archive).await.unwrap()
+        );
+        tokio::fs::write(release.join(SHASUMS_FILENAME), shasums)
+            .await
+            .unwrap();
+
+        let dest = root.join("dest");
+        create_dir_all(&dest).await.unwrap();
+        let (on_progress, events) = progress::channel();
+        let result = fetch_native(FetchConfig {
+            dest: dest.to_string_lossy().to_string(),
+            mirrors: vec![root.join("mirror").to_string_lossy().to_string().into()],
+            unofficial: None,
+            version: "20.11.1".into(),
+            platform: Some(Platform::new(Os::Windows, "x64")),
+            proxy: None,
+            no_proxy: None,
+            timeouts: None,
+            retry: None,
+            tls: None,
+            transport: None,
+            signature: Some(SignatureMode::Skip),
+            cache_dir: None,
+            streaming: None,
+            rate_limit: Some(RateLimit::new(Some(10_000_000))),
+            cancel_signal: None,
+            on_progress,
+        })
+        .await
+        .unwrap();
+        assert_eq!(result.signature, SignatureStatus::Skipped);
+        assert!(dest.join("20.11.1").join("node.exe").is_file());
+
+        let mut phases: Vec<&str> = events
+            .map(|event| match event {
+                Progress::Resolving { .. } => "resolving",
+                Progress::Connecting { .. } => "connecting",
+                Progress::Downloading(_) => "downloading",
+                Progress::Verifying => "verifying",
+                Progress::Extracting(_) => "extracting",
+                Progress::Finalizing => "finalizing",
+                Progress::Retrying { .. } => "retrying",
+            })
+            .collect()
+            .await;
+        phases.dedup();
+        assert_eq!(
+            phases,
+            [
+                "resolving",
+                "connecting",
+                "downloading",
+                "verifying",
+                "extracting",
+                "finalizing"
+            ]
+        );
+
+        let _ = tokio::fs::remove_dir_all(&root).await;
+    }
 }
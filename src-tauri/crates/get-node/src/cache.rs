@@ -0,0 +1,335 @@
+use std::{
+    cmp::Reverse,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{create_dir_all, read_dir, remove_file};
+
+use super::archive::{sha256_file, SignatureStatus, PARTIAL_META_SUFFIX};
+
+/// Suffix of the metadata saved next to a cached archive
+const META_SUFFIX: &str = ".json";
+
+/// A downloaded archive kept in the cache
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CacheEntry {
+    /// the mirror the archive was downloaded from
+    pub mirror: String,
+
+    /// node version
+    pub version: String,
+
+    /// archive filename, e.g. `node-v20.11.0-linux-x64.tar.gz`
+    pub filename: String,
+
+    /// archive path in the cache
+    pub path: String,
+
+    /// archive size in bytes
+    pub size: u64,
+
+    /// sha256 verified against `SHASUMS256.txt` when downloaded
+    pub sha256: String,
+
+    /// signature verification result of `SHASUMS256.txt` when downloaded
+    pub signature: SignatureStatus,
+
+    /// download time (unix timestamp in seconds)
+    pub created_at: u64,
+
+    /// last time the entry was used to install (unix timestamp in seconds)
+    pub used_at: u64,
+}
+
+impl CacheEntry {
+    fn meta_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}{}", self.path, META_SUFFIX))
+    }
+
+    async fn save(&self) -> Result<()> {
+        tokio::fs::write(self.meta_path(), serde_json::to_vec(self)?).await?;
+        Ok(())
+    }
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// The directory name of a mirror inside the cache
+/// e.g. `https://nodejs.org/dist` -> `nodejs.org_dist`
+pub fn mirror_key(mirror: &str) -> String {
+    let mirror = mirror
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or(mirror);
+    mirror
+        .trim_end_matches('/')
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// The cache path of an archive, keyed by mirror and archive filename
+/// (the filename carries the version, os and arch)
+pub fn archive_path(cache_dir: &Path, mirror: &str, filename: &str) -> PathBuf {
+    cache_dir.join(mirror_key(mirror)).join(filename)
+}
+
+async fn read_entry(meta_path: &Path) -> Option<CacheEntry> {
+    let content = tokio::fs::read(meta_path).await.ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+/// Find a valid entry in the cache
+/// an archive whose sha256 no longer matches is removed
+pub(crate) async fn lookup(cache_dir: &Path, mirror: &str, filename: &str) -> Option<CacheEntry> {
+    let path = archive_path(cache_dir, mirror, filename);
+    let meta_path = PathBuf::from(format!("{}{}", path.display(), META_SUFFIX));
+    let mut entry = read_entry(&meta_path).await?;
+
+    match sha256_file(&path).await {
+        Ok(actual) if actual.eq_ignore_ascii_case(&entry.sha256) => {}
+        _ => {
+            let _ = remove(&entry).await;
+            return None;
+        }
+    }
+
+    entry.used_at = now();
+    let _ = entry.save().await;
+
+    Some(entry)
+}
+
+/// The directory where `filename` is downloaded to before being recorded in the cache
+pub(crate) async fn prepare(cache_dir: &Path, mirror: &str, filename: &str) -> Result<PathBuf> {
+    let path = archive_path(cache_dir, mirror, filename);
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).await?;
+    }
+
+    Ok(path)
+}
+
+/// Record a downloaded (and verified) archive in the cache
+pub(crate) async fn store(
+    path: &Path,
+    mirror: &str,
+    version: &str,
+    sha256: &str,
+    signature: &SignatureStatus,
+) -> Result<CacheEntry> {
+    let size = tokio::fs::metadata(path).await?.len();
+    let timestamp = now();
+    let entry = CacheEntry {
+        mirror: mirror.to_string(),
+        version: version.to_string(),
+        filename: path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: path.to_string_lossy().to_string(),
+        size,
+        sha256: sha256.to_lowercase(),
+        signature: signature.clone(),
+        created_at: timestamp,
+        used_at: timestamp,
+    };
+    entry.save().await?;
+
+    Ok(entry)
+}
+
+/// List all the entries in the cache, the most recently used first
+pub async fn list(cache_dir: &Path) -> Result<Vec<CacheEntry>> {
+    let mut list = vec![];
+    if !cache_dir.exists() {
+        return Ok(list);
+    }
+
+    let mut mirrors = read_dir(cache_dir).await?;
+    while let Some(mirror) = mirrors.next_entry().await? {
+        if !mirror.file_type().await?.is_dir() {
+            continue;
+        }
+
+        let mut files = read_dir(mirror.path()).await?;
+        while let Some(file) = files.next_entry().await? {
+            let name = file.file_name().to_string_lossy().to_string();
+            if !name.ends_with(META_SUFFIX) || name.ends_with(PARTIAL_META_SUFFIX) {
+                continue;
+            }
+            if let Some(entry) = read_entry(&file.path()).await {
+                list.push(entry);
+            }
+        }
+    }
+    list.sort_by_key(|entry| Reverse(entry.used_at));
+
+    Ok(list)
+}
+
+/// Remove an entry (archive & metadata) from the cache
+pub async fn remove(entry: &CacheEntry) -> Result<()> {
+    let (r_archive, r_meta) =
+        tokio::join!(remove_file(&entry.path), remove_file(entry.meta_path()));
+    // the archive may already be gone
+    if let Err(err) = r_archive {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            return Err(err.into());
+        }
+    }
+    r_meta?;
+
+    Ok(())
+}
+
+/// Remove the least recently used entries until the cache is not larger than `max_size` (bytes)
+/// return the removed entries
+pub async fn prune(cache_dir: &Path, max_size: u64) -> Result<Vec<CacheEntry>> {
+    let list = list(cache_dir).await?;
+    let mut total_size: u64 = list.iter().map(|entry| entry.size).sum();
+
+    let mut removed = vec![];
+    for entry in list.into_iter().rev() {
+        if total_size <= max_size {
+            break;
+        }
+        remove(&entry).await?;
+        total_size = total_size.saturating_sub(entry.size);
+        removed.push(entry);
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_key() {
+        assert_eq!(mirror_key("https://nodejs.org/dist"), "nodejs.org_dist");
+        assert_eq!(
+            mirror_key("https://npmmirror.com/mirrors/node/"),
+            "npmmirror.com_mirrors_node"
+        );
+        assert_eq!(
+            mirror_key("http://10.0.0.1:8081/artifactory/node"),
+            "10.0.0.1_8081_artifactory_node"
+        );
+    }
+
+    const MIRROR: &str = "https://nodejs.org/dist";
+
+    /// Write `content` to the cache as `filename` & record it, last used at `used_at`
+    async fn add(cache_dir: &Path, filename: &str, content: &[u8], used_at: u64) -> CacheEntry {
+        let path = prepare(cache_dir, MIRROR, filename).await.unwrap();
+        tokio::fs::write(&path, content).await.unwrap();
+        let sha256 = sha256_file(&path).await.unwrap();
+        let mut entry = store(&path, MIRROR, "20.11.1", &sha256, &SignatureStatus::Skipped)
+            .await
+            .unwrap();
+        entry.used_at = used_at;
+        entry.save().await.unwrap();
+        entry
+    }
+
+    async fn temp_dir(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(name);
+        let _ = tokio::fs::remove_dir_all(&root).await;
+        root
+    }
+
+    fn filenames(list: &[CacheEntry]) -> Vec<&str> {
+        list.iter().map(|entry| entry.filename.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_lookup() {
+        let root = temp_dir("get-node-test-cache-lookup").await;
+        let entry = add(&root, "node-v20.11.1-linux-x64.tar.xz", b"archive", 1).await;
+
+        let found = lookup(&root, MIRROR, &entry.filename).await.unwrap();
+        assert_eq!(found.sha256, entry.sha256);
+        assert!(found.used_at > 1);
+        assert_eq!(list(&root).await.unwrap()[0].used_at, found.used_at);
+        assert!(lookup(&root, MIRROR, "node-v22.0.0-linux-x64.tar.xz")
+            .await
+            .is_none());
+
+        // an archive whose sha256 no longer matches is removed
+        tokio::fs::write(&entry.path, b"corrupted").await.unwrap();
+        assert!(lookup(&root, MIRROR, &entry.filename).await.is_none());
+        assert!(!Path::new(&entry.path).exists());
+        assert!(list(&root).await.unwrap().is_empty());
+
+        let _ = tokio::fs::remove_dir_all(&root).await;
+    }
+
+    #[tokio::test]
+    async fn test_list() {
+        let root = temp_dir("get-node-test-cache-list").await;
+        assert!(list(&root).await.unwrap().is_empty());
+
+        let older = add(&root, "node-v20.11.1-linux-x64.tar.xz", b"older", 10).await;
+        add(&root, "node-v22.1.0-linux-x64.tar.xz", b"newer", 20).await;
+        // an interrupted download is not an entry
+        let part = format!("{}.part", older.path.replace("20.11.1", "18.19.0"));
+        tokio::fs::write(&part, b"partial").await.unwrap();
+        tokio::fs::write(format!("{}.json", part), b"{}")
+            .await
+            .unwrap();
+        tokio::fs::write(root.join("stray.json"), b"{}")
+            .await
+            .unwrap();
+
+        let list = list(&root).await.unwrap();
+        assert_eq!(
+            filenames(&list),
+            [
+                "node-v22.1.0-linux-x64.tar.xz",
+                "node-v20.11.1-linux-x64.tar.xz"
+            ]
+        );
+        assert_eq!(list[1].size, 5);
+        assert_eq!(list[1].mirror, MIRROR);
+
+        let _ = tokio::fs::remove_dir_all(&root).await;
+    }
+
+    #[tokio::test]
+    async fn test_prune() {
+        let root = temp_dir("get-node-test-cache-prune").await;
+        add(&root, "a.tar.xz", b"1234", 1).await;
+        add(&root, "b.tar.xz", b"1234", 3).await;
+        add(&root, "c.tar.xz", b"1234", 2).await;
+
+        // the least recently used first
+        assert_eq!(filenames(&prune(&root, 8).await.unwrap()), ["a.tar.xz"]);
+        assert!(prune(&root, 8).await.unwrap().is_empty());
+        assert_eq!(
+            filenames(&list(&root).await.unwrap()),
+            ["b.tar.xz", "c.tar.xz"]
+        );
+
+        assert_eq!(
+            filenames(&prune(&root, 0).await.unwrap()),
+            ["c.tar.xz", "b.tar.xz"]
+        );
+        assert!(list(&root).await.unwrap().is_empty());
+        assert!(!root.join(mirror_key(MIRROR)).join("a.tar.xz").exists());
+
+        let _ = tokio::fs::remove_dir_all(&root).await;
+    }
+}
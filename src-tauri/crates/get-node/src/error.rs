@@ -0,0 +1,68 @@
+use std::fmt;
+
+/// Errors that callers may want to tell apart from a generic failure
+/// (e.g. to show a dedicated message in the UI)
+///
+/// They are returned wrapped in `anyhow::Error`,
+/// use `err.downcast_ref::<get_node::Error>()` to inspect them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// the downloaded archive does not match its `SHASUMS256.txt` entry
+    ChecksumMismatch {
+        file: String,
+        expected: String,
+        actual: String,
+    },
+
+    /// `SHASUMS256.txt` is not signed by a Node.js release key
+    InvalidSignature { reason: String },
+
+    /// `SHASUMS256.txt` lists none of the archives of the platform
+    ArchiveNotFound { filenames: Vec<String> },
+
+    /// no installed or released version matches the specifier
+    VersionNotFound { spec: String },
+
+    /// the version directory exists already, uninstall it first
+    AlreadyInstalled { version: String, path: String },
+
+    /// the TLS handshake with the mirror failed, e.g. its certificate is signed by an unknown CA
+    Tls { host: String, reason: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ChecksumMismatch {
+                file,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Checksum mismatch for \"{file}\" (expected {expected}, got {actual}), the archive may be corrupted or tampered with"
+            ),
+            Error::InvalidSignature { reason } => write!(
+                f,
+                "Failed to verify the signature of the release checksums: {reason}"
+            ),
+            Error::ArchiveNotFound { filenames } => write!(
+                f,
+                "No checksum found for \"{}\" in SHASUMS256.txt, the mirror has no archive for this platform",
+                filenames.join("\", \"")
+            ),
+            Error::VersionNotFound { spec } => {
+                write!(f, "No node version matches \"{spec}\"")
+            }
+            Error::AlreadyInstalled { version, path } => write!(
+                f,
+                "Node v{version} is already installed in \"{path}\", uninstall it first to reinstall"
+            ),
+            Error::Tls { host, reason } => write!(
+                f,
+                "TLS failure with \"{host}\" ({reason}), add its CA certificate if it uses a private CA"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,259 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::fs::{read_dir, remove_dir_all, remove_file, rename};
+
+use super::{
+    archive::{validate, PARTIAL_META_SUFFIX, PARTIAL_SUFFIX, STAGING_PREFIX},
+    platform::{Os, Platform},
+};
+
+/// The archive extensions an install may leave behind
+const ARCHIVE_EXTENSIONS: &[&str] = &[".tar.xz", ".tar.gz", ".zip"];
+
+/// What an interrupted install left in the install directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LeftoverKind {
+    /// a downloaded (or partially downloaded) archive, e.g. `node-v20.11.1-linux-x64.tar.xz.part`
+    Archive,
+
+    /// a staging directory, e.g. `.staging-20.11.1`
+    Staging,
+
+    /// an unpacked directory that was never renamed to its version, e.g. `node-v20.11.1-linux-x64`
+    Unrenamed,
+}
+
+/// An orphan of an interrupted install
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Leftover {
+    pub kind: LeftoverKind,
+
+    /// the file or directory path
+    pub path: String,
+
+    /// the size on disk in bytes
+    pub size: u64,
+
+    /// the node version it belongs to, if known
+    pub version: Option<String>,
+
+    /// whether the install can be finished by renaming it to its version,
+    /// i.e. it contains a node binary and the version is not installed
+    pub finishable: bool,
+}
+
+/// Find the orphans of interrupted installs in the install directory `dest`
+pub async fn scan(dest: &Path) -> Result<Vec<Leftover>> {
+    if !dest.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut leftovers = vec![];
+    let mut entries = read_dir(dest).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = entry.path();
+        let is_dir = entry.file_type().await?.is_dir();
+
+        let (kind, version) = if is_dir {
+            if let Some(version) = name.strip_prefix(STAGING_PREFIX) {
+                (LeftoverKind::Staging, Some(version.to_string()))
+            } else if let Some(version) = archive_version(&name) {
+                (LeftoverKind::Unrenamed, Some(version))
+            } else {
+                continue;
+            }
+        } else {
+            let archive = name
+                .strip_suffix(PARTIAL_META_SUFFIX)
+                .or_else(|| name.strip_suffix(PARTIAL_SUFFIX))
+                .unwrap_or(&name);
+            if !ARCHIVE_EXTENSIONS.iter().any(|ext| archive.ends_with(ext)) {
+                continue;
+            }
+            match archive_version(archive) {
+                Some(version) => (LeftoverKind::Archive, Some(version)),
+                None => continue,
+            }
+        };
+
+        let finishable = kind == LeftoverKind::Unrenamed
+            && has_node_binary(&path)
+            && version
+                .as_deref()
+                .is_some_and(|version| !dest.join(version).exists());
+        let size = size_of(path.clone()).await;
+        leftovers.push(Leftover {
+            kind,
+            path: path.to_string_lossy().to_string(),
+            size,
+            version,
+            finishable,
+        });
+    }
+
+    Ok(leftovers)
+}
+
+/// Remove a leftover
+pub async fn clean(leftover: &Leftover) -> Result<()> {
+    let path = Path::new(&leftover.path);
+    if path.is_dir() {
+        remove_dir_all(path).await?;
+    } else if path.exists() {
+        remove_file(path).await?;
+    }
+    Ok(())
+}
+
+/// Finish the install of an unrenamed directory by renaming it to its version,
+/// once it passes the same validation as an install
+/// return the path of the installed version
+pub async fn finish(leftover: &Leftover) -> Result<PathBuf> {
+    let path = Path::new(&leftover.path);
+    let (LeftoverKind::Unrenamed, Some(version)) = (leftover.kind, leftover.version.as_deref())
+    else {
+        bail!("Only an unpacked directory can be finished: {:?}", path);
+    };
+
+    let target = path.with_file_name(version);
+    if target.exists() {
+        bail!("Node v{} is already installed in {:?}", version, target);
+    }
+    let platform = path
+        .file_name()
+        .and_then(|name| archive_platform(&name.to_string_lossy()))
+        .with_context(|| format!("Unknown platform of {:?}, it can only be removed", path))?;
+    validate(path, version, &platform)
+        .await
+        .with_context(|| format!("Invalid install in {:?}, it can only be removed", path))?;
+    rename(path, &target).await?;
+    Ok(target)
+}
+
+/// The version of an archive filename or of the directory inside it,
+/// e.g. `node-v20.11.1-linux-x64.tar.xz` -> `20.11.1`
+fn archive_version(name: &str) -> Option<String> {
+    let (version, _) = name.strip_prefix("node-v")?.split_once('-')?;
+    node_semver::Version::parse(version).ok()?;
+    Some(version.to_string())
+}
+
+/// The platform of an unpacked directory, e.g. `node-v20.11.1-linux-x64` -> `linux-x64`
+fn archive_platform(name: &str) -> Option<Platform> {
+    let (_, platform) = name.strip_prefix("node-v")?.split_once('-')?;
+    let (os, arch) = platform.split_once('-')?;
+    let os = [Os::Linux, Os::Darwin, Os::Windows, Os::Aix]
+        .into_iter()
+        .find(|candidate| candidate.distro_name() == os)?;
+    Some(Platform::new(os, arch))
+}
+
+fn has_node_binary(path: &Path) -> bool {
+    path.join("bin").join("node").is_file() || path.join("node.exe").is_file()
+}
+
+/// The size of a file, or of all the files in a directory
+async fn size_of(path: PathBuf) -> u64 {
+    tokio::task::spawn_blocking(move || {
+        let mut size = 0;
+        let mut stack = vec![path];
+        while let Some(path) = stack.pop() {
+            let Ok(metadata) = std::fs::symlink_metadata(&path) else {
+                continue;
+            };
+            if !metadata.is_dir() {
+                size += metadata.len();
+                continue;
+            }
+            if let Ok(entries) = std::fs::read_dir(&path) {
+                stack.extend(entries.flatten().map(|entry| entry.path()));
+            }
+        }
+        size
+    })
+    .await
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_version() {
+        assert_eq!(
+            archive_version("node-v20.11.1-linux-x64.tar.xz").as_deref(),
+            Some("20.11.1")
+        );
+        assert_eq!(
+            archive_version("node-v18.20.3-darwin-arm64").as_deref(),
+            Some("18.20.3")
+        );
+        assert_eq!(archive_version("node-vX-linux-x64"), None);
+        assert_eq!(archive_version("20.11.1"), None);
+    }
+
+    #[test]
+    fn test_archive_platform() {
+        assert_eq!(
+            archive_platform("node-v20.11.1-linux-x64"),
+            Some(Platform::new(Os::Linux, "x64"))
+        );
+        assert_eq!(
+            archive_platform("node-v20.11.1-linux-x64-musl"),
+            Some(Platform::new(Os::Linux, "x64-musl"))
+        );
+        assert_eq!(
+            archive_platform("node-v20.11.1-win-arm64"),
+            Some(Platform::new(Os::Windows, "arm64"))
+        );
+        assert_eq!(archive_platform("node-v20.11.1-plan9-x64"), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_finish() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dest = std::env::temp_dir().join(format!("get-node-janitor-{}", std::process::id()));
+        let _ = remove_dir_all(&dest).await;
+        let current = Platform::current();
+        let version = node_semver::Version::parse("20.11.1").unwrap();
+        let name = format!(
+            "node-v20.11.1-{}-{}",
+            current.os.distro_name(),
+            current.archive_arch(&version)
+        );
+        let binary = dest.join(&name).join("bin").join("node");
+        tokio::fs::create_dir_all(binary.parent().unwrap())
+            .await
+            .unwrap();
+
+        let leftover = Leftover {
+            kind: LeftoverKind::Unrenamed,
+            path: dest.join(&name).to_string_lossy().to_string(),
+            size: 0,
+            version: Some("20.11.1".into()),
+            finishable: true,
+        };
+        let write_binary = |reported: &str| {
+            std::fs::write(&binary, format!("#!/bin/sh\necho {reported}\n")).unwrap();
+            std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        };
+
+        // a binary reporting another version is not moved into place
+        write_binary("v18.0.0");
+        assert!(finish(&leftover).await.is_err());
+        assert!(!dest.join("20.11.1").exists());
+
+        write_binary("v20.11.1");
+        assert_eq!(finish(&leftover).await.unwrap(), dest.join("20.11.1"));
+        assert!(dest.join("20.11.1").join("bin").join("node").is_file());
+
+        let _ = remove_dir_all(&dest).await;
+    }
+}
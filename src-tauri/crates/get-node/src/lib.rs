@@ -1,12 +1,20 @@
-use serde::{Deserialize, Serialize};
-
 pub mod archive;
+pub mod cache;
+mod error;
+pub mod janitor;
 pub mod list;
-mod node;
+pub mod mirror;
+pub mod net;
+pub mod node;
+pub mod platform;
+pub mod progress;
+pub mod proxy;
+pub mod release;
+pub mod resolver;
+pub mod tls;
+pub mod transport;
 
-#[derive(Debug, Default, Clone, Deserialize, Serialize)]
-pub struct Proxy {
-    pub enabled: bool,
-    pub ip: String,
-    pub port: String,
-}
+pub use error::Error;
+pub use progress::{Progress, Transfer};
+pub use proxy::{Proxy, ProxyMode, ProxyScheme};
+pub use transport::{FileTransport, Transport};
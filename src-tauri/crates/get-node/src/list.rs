@@ -1,12 +1,30 @@
+use std::sync::Arc;
+
 use anyhow::Result;
-use serde::de::DeserializeOwned;
-use std::time::Duration;
+use reqwest::{
+    header::{HeaderName, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use super::Proxy;
+use super::{
+    cache,
+    mirror::{self, Endpoint, Failures, Mirror},
+    net::{self, Retrier, Retry, Timeouts},
+    platform::Platform,
+    release::Releases,
+    tls::Tls,
+    transport::{self, Transport},
+    Proxy,
+};
 
 pub struct ListConfig {
-    /// fetch mirror url
-    pub mirror: Option<String>,
+    /// fetch mirrors, tried in turn until one of them answers
+    pub mirrors: Vec<Mirror>,
+
+    /// unofficial builds mirrors, their index is used instead
+    /// when the official builds do not run on this system (musl, riscv64, loong64)
+    pub unofficial: Option<Vec<Mirror>>,
 
     /// disable proxy
     pub no_proxy: Option<bool>,
@@ -14,56 +32,292 @@ pub struct ListConfig {
     /// proxy ip & port
     pub proxy: Option<Proxy>,
 
-    /// timeout
-    pub timeout: Option<Duration>,
+    /// connect, read-idle & total timeouts
+    /// default value is `Timeouts::default()`
+    pub timeouts: Option<Timeouts>,
+
+    /// how the requests are retried on transient failures
+    /// default value is `Retry::default()`, the benchmark never retries
+    pub retry: Option<Retry>,
+
+    /// extra CA certificates & OS trust store
+    pub tls: Option<Tls>,
+
+    /// how the requests are sent, `None` to pick it for each mirror:
+    /// `FileTransport` for a `file://` url or a local directory, an http client otherwise
+    pub transport: Option<Arc<dyn Transport>>,
+}
+
+/// The validators & origin of a fetched version index,
+/// kept with the cached index to only download it again when it changed
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct IndexMeta {
+    /// `ETag` of the response
+    pub etag: Option<String>,
+
+    /// `Last-Modified` of the response
+    pub last_modified: Option<String>,
+
+    /// when the index was fetched or last revalidated, in seconds since the unix epoch
+    pub fetched_at: u64,
+
+    /// index base url of the mirror it came from
+    pub mirror: String,
+}
+
+impl IndexMeta {
+    /// seconds since the index was fetched or last revalidated
+    pub fn age(&self) -> u64 {
+        cache::now().saturating_sub(self.fetched_at)
+    }
+}
+
+/// The result of a conditional fetch of the version index
+#[derive(Debug)]
+pub enum Fetched<T> {
+    /// the index changed, or there was nothing cached
+    Modified(T, IndexMeta),
+
+    /// the cached index is still up to date, with its refreshed metadata
+    NotModified(IndexMeta),
 }
 
 pub async fn version_list<T>(config: ListConfig) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    match version_list_if_modified(config, None).await? {
+        Fetched::Modified(list, _) => Ok(list),
+        Fetched::NotModified(_) => anyhow::bail!("The mirror answered `304` to a plain request"),
+    }
+}
+
+/// Fetch the version index unless it did not change since `cached` was fetched
+///
+/// `If-None-Match` & `If-Modified-Since` are only sent to the mirror `cached` came from,
+/// the index of another mirror is downloaded in full.
+pub async fn version_list_if_modified<T>(
+    config: ListConfig,
+    cached: Option<&IndexMeta>,
+) -> Result<Fetched<T>>
 where
     T: DeserializeOwned,
 {
     let ListConfig {
-        mirror,
-        timeout,
+        mirrors,
+        unofficial,
+        timeouts,
+        retry,
         no_proxy,
         proxy,
+        tls,
+        transport: custom,
     } = config;
 
-    if mirror.is_none() {
+    let mirrors = match unofficial {
+        Some(unofficial) if Platform::current().is_unofficial_only() => unofficial,
+        _ => mirrors,
+    };
+    if mirrors.is_empty() {
         anyhow::bail!("mirror should not be null");
     }
 
-    let mirror = mirror.unwrap();
-    // timeout default value is `20s`
-    let timeout = timeout.unwrap_or(Duration::from_millis(20000));
-
-    let mut builder = reqwest::ClientBuilder::new().use_rustls_tls();
-    if let Some(true) = no_proxy {
-        builder = builder.no_proxy();
-    } else if let Some(proxy) = proxy {
-        if proxy.enabled {
-            builder = builder.no_proxy();
-            let proxy_scheme = format!("http://{}:{}", proxy.ip, proxy.port);
-            if let Ok(proxy) = reqwest::Proxy::http(&proxy_scheme) {
-                builder = builder.proxy(proxy);
+    let timeouts = timeouts.unwrap_or_default();
+    let retrier = Retrier::new(retry, None);
+
+    let mut failures = Failures::default();
+    for mirror in mirror::ordered(&mirrors, Endpoint::Index) {
+        let base = mirror.index_base();
+        let cached = cached.filter(|cached| cached.mirror == base);
+        let ret = async {
+            let transport = transport::for_mirror(
+                custom.as_ref(),
+                proxy.clone(),
+                no_proxy,
+                tls.as_ref(),
+                &mirror,
+                Endpoint::Index,
+                timeouts,
+            )
+            .await?;
+            let mut request = transport::get(&format!("{}/index.json", base))?;
+            for (name, value) in validators(cached) {
+                request
+                    .headers_mut()
+                    .insert(name, HeaderValue::from_str(&value)?);
             }
-            if let Ok(proxy) = reqwest::Proxy::https(&proxy_scheme) {
-                builder = builder.proxy(proxy);
+
+            let response = net::send(&*transport, request, &retrier, None)
+                .await?
+                .error_for_status()?;
+            let header = |name: HeaderName| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string)
+            };
+            let meta = IndexMeta {
+                etag: header(ETAG),
+                last_modified: header(LAST_MODIFIED),
+                fetched_at: cache::now(),
+                mirror: base.to_string(),
+            };
+
+            match (response.status(), cached) {
+                (StatusCode::NOT_MODIFIED, Some(cached)) => {
+                    // a `304` may leave the validators out, the cached ones are still valid
+                    Ok::<_, anyhow::Error>(Fetched::NotModified(IndexMeta {
+                        etag: meta.etag.or_else(|| cached.etag.clone()),
+                        last_modified: meta.last_modified.or_else(|| cached.last_modified.clone()),
+                        ..meta
+                    }))
+                }
+                _ => Ok(Fetched::Modified(response.json::<T>().await?, meta)),
             }
-            if let Ok(proxy) = reqwest::Proxy::all(&proxy_scheme) {
-                builder = builder.proxy(proxy);
+        }
+        .await;
+
+        match ret {
+            Ok(fetched) => {
+                mirror::report_success(base);
+                return Ok(fetched);
             }
+            Err(err) => failures.push(base, err),
         }
     }
 
-    let list = builder
-        .timeout(timeout)
-        .build()?
-        .get(format!("{}/index.json", &mirror.trim_end_matches("/")))
-        .send()
-        .await?
-        .json::<T>()
-        .await?;
+    Err(failures.into_error())
+}
+
+/// The conditional request headers of a cached index
+fn validators(cached: Option<&IndexMeta>) -> Vec<(HeaderName, String)> {
+    let Some(cached) = cached else {
+        return vec![];
+    };
+    let mut headers = vec![];
+    if let Some(etag) = cached.etag.as_deref().filter(|etag| !etag.is_empty()) {
+        headers.push((IF_NONE_MATCH, etag.to_string()));
+    }
+    if let Some(date) = cached
+        .last_modified
+        .as_deref()
+        .filter(|date| !date.is_empty())
+    {
+        headers.push((IF_MODIFIED_SINCE, date.to_string()));
+    }
+    headers
+}
+
+/// Fetch the typed version index, the latest release first
+pub async fn release_list(config: ListConfig) -> Result<Releases> {
+    version_list::<Releases>(config).await
+}
+
+/// Fetch the typed version index unless it did not change since `cached` was fetched
+pub async fn release_list_if_modified(
+    config: ListConfig,
+    cached: Option<&IndexMeta>,
+) -> Result<Fetched<Releases>> {
+    version_list_if_modified::<Releases>(config, cached).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validators() {
+        assert!(validators(None).is_empty());
+
+        let cached = IndexMeta {
+            etag: Some("\"5f3c-1a2b\"".into()),
+            last_modified: Some("Tue, 16 Apr 2024 09:00:00 GMT".into()),
+            fetched_at: 0,
+            mirror: "https://nodejs.org/dist".into(),
+        };
+        let headers = validators(Some(&cached));
+        assert_eq!(headers[0], (IF_NONE_MATCH, "\"5f3c-1a2b\"".to_string()));
+        assert_eq!(
+            headers[1],
+            (
+                IF_MODIFIED_SINCE,
+                "Tue, 16 Apr 2024 09:00:00 GMT".to_string()
+            )
+        );
+
+        let cached = IndexMeta {
+            etag: Some("".into()),
+            ..cached
+        };
+        assert_eq!(validators(Some(&cached)).len(), 1);
+    }
 
-    Ok(list)
+    fn config(mirrors: Vec<Mirror>, transport: Option<Arc<dyn Transport>>) -> ListConfig {
+        ListConfig {
+            mirrors,
+            unofficial: None,
+            no_proxy: None,
+            proxy: None,
+            timeouts: None,
+            retry: None,
+            tls: None,
+            transport,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_version_list_local_mirror() {
+        let root = std::env::temp_dir().join("get-node-test-list-local-mirror");
+        tokio::fs::create_dir_all(&root).await.unwrap();
+        tokio::fs::write(root.join("index.json"), r#"[{"version":"v20.11.1"}]"#)
+            .await
+            .unwrap();
+        let mirrors = vec![Mirror::new(root.to_string_lossy().to_string())];
+
+        let Fetched::Modified(list, meta) =
+            version_list_if_modified::<serde_json::Value>(config(mirrors.clone(), None), None)
+                .await
+                .unwrap()
+        else {
+            panic!("the index should be fetched");
+        };
+        assert_eq!(list[0]["version"], "v20.11.1");
+        assert!(meta.etag.is_some());
+
+        // unchanged since, it is not read again
+        let fetched =
+            version_list_if_modified::<serde_json::Value>(config(mirrors, None), Some(&meta))
+                .await
+                .unwrap();
+        assert!(matches!(fetched, Fetched::NotModified(_)));
+
+        let _ = tokio::fs::remove_dir_all(&root).await;
+    }
+
+    /// Serve a canned index without any network
+    struct Mock;
+
+    impl Transport for Mock {
+        fn send(&self, request: reqwest::Request) -> transport::Sending<'_> {
+            Box::pin(async move {
+                assert_eq!(
+                    request.url().as_str(),
+                    "https://mock.invalid/node/index.json"
+                );
+                Ok(http::Response::new(r#"[{"version":"v22.0.0"}]"#).into())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_version_list_custom_transport() {
+        let list: serde_json::Value = version_list(config(
+            vec!["https://mock.invalid/node/".into()],
+            Some(Arc::new(Mock)),
+        ))
+        .await
+        .unwrap();
+        assert_eq!(list[0]["version"], "v22.0.0");
+    }
 }
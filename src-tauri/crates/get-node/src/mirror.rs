@@ -0,0 +1,523 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard, OnceLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Error, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures_util::future::join_all;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use serde::{Deserialize, Serialize};
+
+use super::{list::ListConfig, transport};
+
+/// The mirror of the unofficial builds project (musl, riscv64, loong64, glibc 2.17 ...)
+pub const UNOFFICIAL_MIRROR: &str = "https://unofficial-builds.nodejs.org/download/release";
+
+/// A mirror that failed is tried after the others for this long
+const COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+/// A node distribution mirror
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Mirror {
+    /// mirror url, e.g. `https://nodejs.org/dist`
+    pub url: String,
+
+    /// base url of `index.json`, defaults to `url`
+    pub index_url: Option<String>,
+
+    /// base url of `SHASUMS256.txt` and the archives, defaults to `url`
+    pub download_url: Option<String>,
+
+    /// DANGEROUS: do not verify the TLS certificate of the mirror,
+    /// anyone on the network can then serve the index & the archives
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub insecure: bool,
+
+    /// the credentials sent with every request to the mirror
+    /// never serialized, so that they do not end up in a settings file,
+    /// the caller keeps them somewhere safe and sets them before use
+    #[serde(default, skip_serializing)]
+    pub credentials: Option<Credentials>,
+}
+
+/// How to authenticate to a private mirror (Artifactory, Nexus, GitLab ...)
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Credentials {
+    /// the user of the basic auth, with `password`
+    pub username: Option<String>,
+
+    pub password: Option<String>,
+
+    /// a bearer token, used instead of the basic auth when both are set
+    pub token: Option<String>,
+
+    /// extra headers, e.g. `X-JFrog-Art-Api` or `PRIVATE-TOKEN`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Credentials {
+    /// Whether there is nothing to send
+    pub fn is_empty(&self) -> bool {
+        let empty = |value: &Option<String>| value.as_deref().unwrap_or_default().is_empty();
+        empty(&self.username) && empty(&self.token) && self.headers.is_empty()
+    }
+
+    /// The headers sent with every request, marked sensitive so that they are never logged
+    pub(crate) fn headers(&self) -> Result<HeaderMap> {
+        let sensitive = |value: &str| {
+            HeaderValue::from_str(value).map(|mut value| {
+                value.set_sensitive(true);
+                value
+            })
+        };
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let value = sensitive(value.trim())
+                .with_context(|| format!("Invalid value of the header \"{}\"", name))?;
+            let name = HeaderName::from_bytes(name.trim().as_bytes())
+                .with_context(|| format!("Invalid header name \"{}\"", name))?;
+            headers.insert(name, value);
+        }
+
+        let filled = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let authorization = match (filled(&self.token), filled(&self.username)) {
+            (Some(token), _) => Some(format!("Bearer {}", token)),
+            (None, Some(username)) => {
+                let password = self.password.as_deref().unwrap_or_default();
+                Some(format!(
+                    "Basic {}",
+                    STANDARD.encode(format!("{}:{}", username, password))
+                ))
+            }
+            (None, None) => None,
+        };
+        if let Some(authorization) = authorization {
+            headers.insert(
+                AUTHORIZATION,
+                sensitive(&authorization).context("Invalid credentials of the mirror")?,
+            );
+        }
+        Ok(headers)
+    }
+}
+
+/// What a mirror is used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endpoint {
+    /// the version index (`index.json`)
+    Index,
+
+    /// checksums & archives
+    Download,
+}
+
+impl Mirror {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ..Self::default()
+        }
+    }
+
+    /// base url of `index.json`, without the trailing `/`
+    pub fn index_base(&self) -> &str {
+        Self::trim(self.index_url.as_deref().unwrap_or_default(), &self.url)
+    }
+
+    /// base url of `SHASUMS256.txt` and the archives, without the trailing `/`
+    pub fn download_base(&self) -> &str {
+        Self::trim(self.download_url.as_deref().unwrap_or_default(), &self.url)
+    }
+
+    /// base url used for `endpoint`
+    pub fn base(&self, endpoint: Endpoint) -> &str {
+        match endpoint {
+            Endpoint::Index => self.index_base(),
+            Endpoint::Download => self.download_base(),
+        }
+    }
+
+    fn trim<'a>(url: &'a str, default: &'a str) -> &'a str {
+        let url = url.trim();
+        let url = if url.is_empty() { default.trim() } else { url };
+        url.trim_end_matches('/')
+    }
+}
+
+impl From<&str> for Mirror {
+    fn from(url: &str) -> Self {
+        Self::new(url)
+    }
+}
+
+impl From<String> for Mirror {
+    fn from(url: String) -> Self {
+        Self::new(url)
+    }
+}
+
+/// Health of a mirror base url in this process
+#[derive(Debug, Default, Clone)]
+struct Health {
+    /// consecutive failures
+    failures: u32,
+
+    last_failure: Option<Instant>,
+
+    last_success: Option<Instant>,
+}
+
+impl Health {
+    /// failed recently and has not recovered since
+    fn cooling_down(&self) -> bool {
+        match (self.last_failure, self.last_success) {
+            (Some(failure), Some(success)) if success > failure => false,
+            (Some(failure), _) => failure.elapsed() < COOLDOWN,
+            _ => false,
+        }
+    }
+
+    /// the last request succeeded
+    fn good_since(&self) -> Option<Instant> {
+        match (self.last_success, self.last_failure) {
+            (Some(success), Some(failure)) if failure > success => None,
+            (success, _) => success,
+        }
+    }
+}
+
+fn health() -> MutexGuard<'static, HashMap<String, Health>> {
+    static HEALTH: OnceLock<Mutex<HashMap<String, Health>>> = OnceLock::new();
+    HEALTH
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+}
+
+/// Record that `base` served a request
+pub(crate) fn report_success(base: &str) {
+    let mut health = health();
+    let entry = health.entry(base.to_string()).or_default();
+    entry.failures = 0;
+    entry.last_success = Some(Instant::now());
+}
+
+/// Record that a request to `base` failed
+pub(crate) fn report_failure(base: &str) {
+    let mut health = health();
+    let entry = health.entry(base.to_string()).or_default();
+    entry.failures += 1;
+    entry.last_failure = Some(Instant::now());
+}
+
+/// The mirror that served the last successful request for `endpoint`
+pub fn last_good(mirrors: &[Mirror], endpoint: Endpoint) -> Option<Mirror> {
+    let health = health();
+    mirrors
+        .iter()
+        .filter_map(|mirror| {
+            let since = health.get(mirror.base(endpoint))?.good_since()?;
+            Some((since, mirror))
+        })
+        .max_by_key(|(since, _)| *since)
+        .map(|(_, mirror)| mirror.clone())
+}
+
+/// The base urls of the mirrors that served the last successful requests,
+/// saved by the app so that they are tried first again after a restart
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LastGood {
+    /// index base url
+    pub index: Option<String>,
+
+    /// download base url
+    pub download: Option<String>,
+}
+
+impl LastGood {
+    /// The last good ones of `mirrors` in this process
+    pub fn of(mirrors: &[Mirror]) -> Self {
+        Self {
+            index: last_good(mirrors, Endpoint::Index).map(|mirror| mirror.index_base().into()),
+            download: last_good(mirrors, Endpoint::Download)
+                .map(|mirror| mirror.download_base().into()),
+        }
+    }
+
+    /// Record them as good, e.g. the ones saved by the previous run
+    /// what this process found out about them already is kept
+    pub fn restore(&self) {
+        let mut health = health();
+        for base in [&self.index, &self.download].into_iter().flatten() {
+            health.entry(base.clone()).or_insert_with(|| Health {
+                last_success: Some(Instant::now()),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/// The mirrors in the order they should be tried for `endpoint`
+/// the last good one first, then the configured order, the ones that failed recently last
+pub fn ordered(mirrors: &[Mirror], endpoint: Endpoint) -> Vec<Mirror> {
+    let last_good = last_good(mirrors, endpoint);
+    let health = health();
+
+    let mut list: Vec<(usize, &Mirror)> = mirrors.iter().enumerate().collect();
+    list.sort_by_key(|(index, mirror)| {
+        let cooling = health
+            .get(mirror.base(endpoint))
+            .filter(|health| health.cooling_down());
+        (
+            cooling.is_some(),
+            cooling.map(|health| health.failures).unwrap_or_default(),
+            last_good.as_ref() != Some(*mirror),
+            *index,
+        )
+    });
+
+    list.into_iter().map(|(_, mirror)| mirror.clone()).collect()
+}
+
+/// The errors of the mirrors tried in turn
+#[derive(Debug, Default)]
+pub(crate) struct Failures(Vec<(String, Error)>);
+
+impl Failures {
+    /// Record that `base` failed with `err`
+    pub(crate) fn push(&mut self, base: &str, err: Error) {
+        report_failure(base);
+        self.0.push((base.to_string(), err));
+    }
+
+    /// Whether every mirror tried so far has no archive for the platform
+    pub(crate) fn all_not_found(&self) -> bool {
+        !self.0.is_empty()
+            && self.0.iter().all(|(_, err)| {
+                matches!(
+                    err.downcast_ref::<crate::Error>(),
+                    Some(crate::Error::ArchiveNotFound { .. })
+                )
+            })
+    }
+
+    /// The error returned once every mirror failed
+    /// the last error is kept as the source, so it can still be downcast
+    pub(crate) fn into_error(mut self) -> Error {
+        match self.0.len() {
+            0 => anyhow::anyhow!("mirror should not be null"),
+            1 => self.0.remove(0).1,
+            len => {
+                let reasons = self
+                    .0
+                    .iter()
+                    .map(|(base, err)| format!("{base}: {err}"))
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                let (_, last) = self.0.pop().unwrap();
+                last.context(format!("All {len} mirrors failed ({reasons})"))
+            }
+        }
+    }
+}
+
+/// The result of benchmarking a mirror
+#[derive(Debug, Clone, Serialize)]
+pub struct Benchmark {
+    pub mirror: Mirror,
+
+    /// time to fetch `index.json` in milliseconds, `None` if it failed
+    pub elapsed: Option<u64>,
+
+    /// why the mirror failed
+    pub error: Option<String>,
+}
+
+/// Fetch `index.json` from every mirror at the same time
+/// return the results from the fastest to the slowest, the failed mirrors last
+pub async fn benchmark(config: ListConfig) -> Result<Vec<Benchmark>> {
+    let ListConfig {
+        mirrors,
+        timeouts,
+        no_proxy,
+        proxy,
+        tls,
+        transport: custom,
+        ..
+    } = config;
+
+    let timeouts = timeouts.unwrap_or_default();
+
+    let mut list = join_all(mirrors.into_iter().map(|mirror| {
+        let (proxy, tls, custom) = (proxy.clone(), tls.as_ref(), custom.as_ref());
+        async move {
+            // a mirror the transport can not be created for fails like an unreachable one
+            let transport = match transport::for_mirror(
+                custom,
+                proxy,
+                no_proxy,
+                tls,
+                &mirror,
+                Endpoint::Index,
+                timeouts,
+            )
+            .await
+            {
+                Ok(transport) => transport,
+                Err(err) => {
+                    return Benchmark {
+                        mirror,
+                        elapsed: None,
+                        error: Some(err.to_string()),
+                    }
+                }
+            };
+
+            let start = Instant::now();
+            let ret = async {
+                let url = format!("{}/index.json", mirror.index_base());
+                let response = transport.send(transport::get(&url)?).await?;
+                response.error_for_status()?.bytes().await?;
+                Ok::<_, Error>(())
+            }
+            .await;
+
+            match ret {
+                Ok(_) => {
+                    report_success(mirror.index_base());
+                    Benchmark {
+                        mirror,
+                        elapsed: Some(start.elapsed().as_millis() as u64),
+                        error: None,
+                    }
+                }
+                Err(err) => {
+                    report_failure(mirror.index_base());
+                    Benchmark {
+                        mirror,
+                        elapsed: None,
+                        error: Some(err.to_string()),
+                    }
+                }
+            }
+        }
+    }))
+    .await;
+    list.sort_by_key(|benchmark| benchmark.elapsed.unwrap_or(u64::MAX));
+
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_base() {
+        let mirror = Mirror::new("https://nodejs.org/dist/");
+        assert_eq!(mirror.index_base(), "https://nodejs.org/dist");
+        assert_eq!(mirror.download_base(), "https://nodejs.org/dist");
+
+        let mirror = Mirror {
+            url: "https://example.com/node".into(),
+            index_url: Some("https://index.example.com/node/".into()),
+            download_url: Some("".into()),
+            ..Mirror::default()
+        };
+        assert_eq!(mirror.index_base(), "https://index.example.com/node");
+        assert_eq!(mirror.download_base(), "https://example.com/node");
+    }
+
+    #[test]
+    fn test_credentials_headers() {
+        let credentials = Credentials {
+            username: Some("user".into()),
+            password: Some("pass".into()),
+            headers: BTreeMap::from([("X-JFrog-Art-Api".into(), "key".into())]),
+            ..Credentials::default()
+        };
+        let headers = credentials.headers().unwrap();
+        assert_eq!(headers[AUTHORIZATION], "Basic dXNlcjpwYXNz");
+        assert_eq!(headers["x-jfrog-art-api"], "key");
+        assert!(headers[AUTHORIZATION].is_sensitive());
+
+        let credentials = Credentials {
+            token: Some("secret".into()),
+            ..credentials
+        };
+        assert_eq!(
+            credentials.headers().unwrap()[AUTHORIZATION],
+            "Bearer secret"
+        );
+
+        // the credentials are never serialized
+        let mirror = Mirror {
+            credentials: Some(credentials),
+            ..Mirror::new("https://npm.corp/node")
+        };
+        let value = serde_json::to_value(&mirror).unwrap();
+        assert!(value.get("credentials").is_none());
+    }
+
+    #[test]
+    fn test_ordered() {
+        let mirrors: Vec<Mirror> = vec![
+            "https://a.test.invalid/node".into(),
+            "https://b.test.invalid/node".into(),
+            "https://c.test.invalid/node".into(),
+        ];
+        assert_eq!(ordered(&mirrors, Endpoint::Download), mirrors);
+
+        report_failure(mirrors[0].download_base());
+        report_success(mirrors[2].download_base());
+        let list = ordered(&mirrors, Endpoint::Download);
+        assert_eq!(
+            list,
+            vec![mirrors[2].clone(), mirrors[1].clone(), mirrors[0].clone()]
+        );
+        assert_eq!(
+            last_good(&mirrors, Endpoint::Download),
+            Some(mirrors[2].clone())
+        );
+    }
+
+    #[test]
+    fn test_last_good_restore() {
+        let mirrors: Vec<Mirror> = vec![
+            "https://a.restore.invalid/node".into(),
+            Mirror {
+                index_url: Some("https://b.restore.invalid/index".into()),
+                ..Mirror::new("https://b.restore.invalid/node")
+            },
+        ];
+        assert_eq!(LastGood::of(&mirrors), LastGood::default());
+
+        let saved = LastGood {
+            index: Some("https://b.restore.invalid/index".into()),
+            download: None,
+        };
+        saved.restore();
+        assert_eq!(ordered(&mirrors, Endpoint::Index)[0], mirrors[1]);
+        assert_eq!(ordered(&mirrors, Endpoint::Download)[0], mirrors[0]);
+        assert_eq!(LastGood::of(&mirrors), saved);
+
+        // a mirror that failed in this process is not good again
+        report_failure(mirrors[0].download_base());
+        LastGood {
+            index: None,
+            download: Some(mirrors[0].download_base().into()),
+        }
+        .restore();
+        assert_eq!(last_good(&mirrors, Endpoint::Download), None);
+    }
+}
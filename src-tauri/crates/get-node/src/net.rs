@@ -0,0 +1,433 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::ErrorKind,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use reqwest::{header::RETRY_AFTER, Request, Response, StatusCode};
+use tokio::sync::watch;
+
+use super::{
+    progress::{OnProgress, Progress},
+    transport::Transport,
+    Error,
+};
+
+/// The timeouts of the requests to the mirrors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// to connect to the mirror or the proxy, TLS handshake included
+    pub connect: Option<Duration>,
+
+    /// without receiving anything, e.g. a stalled download
+    pub read: Option<Duration>,
+
+    /// the whole request, body included, `None` for no limit so that large downloads
+    /// on slow links are only stopped when they stall
+    pub total: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Some(Duration::from_secs(10)),
+            read: Some(Duration::from_secs(30)),
+            total: None,
+        }
+    }
+}
+
+/// How the idempotent requests are retried on transient failures:
+/// `5xx`, `408`, `429`, refused or reset connections & timeouts,
+/// a download broken off midway is resumed where it stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    /// retries after the first attempt, `0` to never retry
+    pub retries: u32,
+
+    /// the delay before the first retry, doubled for each next one
+    pub base_delay: Duration,
+
+    /// the longest delay between two attempts
+    pub max_delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl Retry {
+    /// The delay before the retry number `retry` (from 1),
+    /// between half of it and all of it so that the clients do not retry all at once
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        delay / 2 + (delay / 2).mul_f64(jitter())
+    }
+}
+
+/// A bandwidth limit, shared by the downloads it is set on & adjustable while they run
+///
+/// A download may be limited by its own limit and by a global one, see `RateLimit::child`.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimit(Arc<Bucket>);
+
+/// How long a throttled download sleeps at most before checking the rate again,
+/// so that a new rate applies right away
+const MAX_THROTTLE: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Default)]
+struct Bucket {
+    /// bytes per second, `0` for no limit
+    rate: AtomicU64,
+
+    state: Mutex<Option<Refill>>,
+
+    /// a limit that applies as well, e.g. the global one of a per-download limit
+    parent: Option<RateLimit>,
+}
+
+#[derive(Debug)]
+struct Refill {
+    /// the bytes that may be transferred right away, negative when overdrawn
+    /// at most a second worth of the rate, the largest burst
+    tokens: f64,
+
+    refilled: Instant,
+}
+
+impl RateLimit {
+    /// A limit of `bytes_per_second`, `None` or `0` for no limit
+    pub fn new(bytes_per_second: Option<u64>) -> Self {
+        let limit = Self::default();
+        limit.set(bytes_per_second);
+        limit
+    }
+
+    /// A limit of `bytes_per_second` that also obeys this one
+    pub fn child(&self, bytes_per_second: Option<u64>) -> Self {
+        Self(Arc::new(Bucket {
+            rate: AtomicU64::new(bytes_per_second.unwrap_or_default()),
+            state: Mutex::default(),
+            parent: Some(self.clone()),
+        }))
+    }
+
+    /// Change the limit, the downloads running apply it right away
+    pub fn set(&self, bytes_per_second: Option<u64>) {
+        self.0
+            .rate
+            .store(bytes_per_second.unwrap_or_default(), Ordering::Relaxed);
+    }
+
+    /// The limit in bytes per second, `None` for no limit
+    pub fn get(&self) -> Option<u64> {
+        Some(self.0.rate.load(Ordering::Relaxed)).filter(|rate| *rate > 0)
+    }
+
+    /// Wait until `bytes` more may be transferred under this limit & its parents
+    pub async fn acquire(&self, bytes: usize) {
+        let mut limit = Some(self);
+        while let Some(current) = limit {
+            current.0.take(bytes).await;
+            limit = current.0.parent.as_ref();
+        }
+    }
+}
+
+impl Bucket {
+    async fn take(&self, bytes: usize) {
+        loop {
+            let rate = self.rate.load(Ordering::Relaxed) as f64;
+            if rate == 0.0 {
+                return;
+            }
+
+            let wait = {
+                let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+                let now = Instant::now();
+                let refill = state.get_or_insert(Refill {
+                    tokens: rate,
+                    refilled: now,
+                });
+                let elapsed = now.duration_since(refill.refilled).as_secs_f64();
+                refill.tokens = (refill.tokens + elapsed * rate).min(rate);
+                refill.refilled = now;
+
+                // a chunk larger than the burst overdraws the bucket, the next ones wait for it
+                if refill.tokens >= 0.0 {
+                    refill.tokens -= bytes as f64;
+                    return;
+                }
+                Duration::from_secs_f64(-refill.tokens / rate)
+            };
+            tokio::time::sleep(wait.min(MAX_THROTTLE)).await;
+        }
+    }
+}
+
+/// The retry policy of a fetch & where its retries are reported
+#[derive(Clone, Copy)]
+pub(crate) struct Retrier<'a> {
+    pub(crate) retry: Retry,
+
+    pub(crate) on_progress: Option<&'a OnProgress>,
+}
+
+impl<'a> Retrier<'a> {
+    pub(crate) fn new(retry: Option<Retry>, on_progress: Option<&'a OnProgress>) -> Self {
+        Self {
+            retry: retry.unwrap_or_default(),
+            on_progress,
+        }
+    }
+
+    /// Report the retry `attempt` & wait for `delay`, unless the fetch is cancelled meanwhile
+    pub(crate) async fn backoff(
+        &self,
+        attempt: u32,
+        delay: Duration,
+        cancel_signal: Option<&mut watch::Receiver<bool>>,
+    ) -> Result<()> {
+        let delay = delay.min(self.retry.max_delay);
+        if let Some(on_progress) = self.on_progress {
+            on_progress(&Progress::Retrying {
+                attempt,
+                retries: self.retry.retries,
+                delay: delay.as_millis() as u64,
+            });
+        }
+        wait(delay, cancel_signal).await
+    }
+}
+
+/// Send an idempotent request, retried on transient failures
+///
+/// The last response is returned as is once the retries are exhausted, even a `5xx` one.
+/// The retries are reported to `on_progress` as `Progress::Retrying`.
+pub(crate) async fn send(
+    transport: &dyn Transport,
+    request: Request,
+    retrier: &Retrier<'_>,
+    mut cancel_signal: Option<&mut watch::Receiver<bool>>,
+) -> Result<Response> {
+    let retry = retrier.retry;
+
+    let mut attempt = 0;
+    loop {
+        // a request with a streamed body can not be sent twice
+        let Some(current) = request.try_clone() else {
+            return send_once(transport, request, cancel_signal).await;
+        };
+
+        let ret = send_once(transport, current, cancel_signal.as_deref_mut()).await;
+        let delay = match &ret {
+            Ok(response) if is_transient_status(response.status()) => {
+                retry_after(response).unwrap_or_else(|| retry.delay(attempt + 1))
+            }
+            Err(err) if is_transient_error(err) => retry.delay(attempt + 1),
+            _ => return ret,
+        };
+        if attempt >= retry.retries {
+            return ret;
+        }
+
+        attempt += 1;
+        retrier
+            .backoff(attempt, delay, cancel_signal.as_deref_mut())
+            .await?;
+    }
+}
+
+async fn send_once(
+    transport: &dyn Transport,
+    request: Request,
+    cancel_signal: Option<&mut watch::Receiver<bool>>,
+) -> Result<Response> {
+    match cancel_signal {
+        Some(cancel_receiver) => {
+            tokio::select! {
+                response = transport.send(request) => {
+                    response
+                },
+                _ = cancel_receiver.changed() => {
+                    bail!("Download was cancelled");
+                }
+            }
+        }
+        None => transport.send(request).await,
+    }
+}
+
+/// Sleep for `delay`, unless the fetch is cancelled meanwhile
+async fn wait(delay: Duration, cancel_signal: Option<&mut watch::Receiver<bool>>) -> Result<()> {
+    match cancel_signal {
+        Some(cancel_receiver) => {
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => Ok(()),
+                _ = cancel_receiver.changed() => bail!("Download was cancelled"),
+            }
+        }
+        None => {
+            tokio::time::sleep(delay).await;
+            Ok(())
+        }
+    }
+}
+
+/// Wait until `limit` lets `bytes` more through, unless the fetch is cancelled meanwhile
+pub(crate) async fn throttle(
+    limit: Option<&RateLimit>,
+    bytes: usize,
+    cancel_signal: Option<&mut watch::Receiver<bool>>,
+) -> Result<()> {
+    let Some(limit) = limit else {
+        return Ok(());
+    };
+    match cancel_signal {
+        Some(cancel_receiver) => {
+            tokio::select! {
+                _ = limit.acquire(bytes) => Ok(()),
+                _ = cancel_receiver.changed() => bail!("Download was cancelled"),
+            }
+        }
+        None => {
+            limit.acquire(bytes).await;
+            Ok(())
+        }
+    }
+}
+
+/// Whether a response status is worth retrying
+fn is_transient_status(status: StatusCode) -> bool {
+    (status.is_server_error() && status != StatusCode::NOT_IMPLEMENTED)
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Whether a request error is worth retrying: timeouts, refused or reset connections,
+/// but not a TLS failure, it fails the same way every time
+fn is_transient_error(err: &anyhow::Error) -> bool {
+    if let Some(Error::Tls { .. }) = err.downcast_ref::<Error>() {
+        return false;
+    }
+    let Some(err) = err.downcast_ref::<reqwest::Error>() else {
+        return false;
+    };
+    if err.is_timeout() || err.is_connect() {
+        return true;
+    }
+
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            if matches!(
+                io.kind(),
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::UnexpectedEof
+                    | ErrorKind::TimedOut
+            ) {
+                return true;
+            }
+        }
+        source = cause.source();
+    }
+    false
+}
+
+/// Whether the response body broke off midway in a way worth resuming,
+/// e.g. a reset connection or a read timeout
+/// (reported as a decoding error by the response stream)
+pub(crate) fn is_transient_body_error(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .is_some_and(|err| err.is_body() || err.is_decode())
+        && is_transient_error(err)
+}
+
+/// The delay asked by a `Retry-After: <seconds>` header
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    seconds.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// A random number in `[0, 1)`, every `RandomState` is seeded differently
+fn jitter() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        let retry = Retry::default();
+        for _ in 0..100 {
+            let delay = retry.delay(1);
+            assert!(delay >= Duration::from_millis(250) && delay <= Duration::from_millis(500));
+
+            let delay = retry.delay(3);
+            assert!(delay >= Duration::from_secs(1) && delay <= Duration::from_secs(2));
+
+            // capped at `max_delay`
+            let delay = retry.delay(20);
+            assert!(delay >= Duration::from_secs(5) && delay <= Duration::from_secs(10));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let global = RateLimit::new(Some(1_000_000));
+        let limit = global.child(None);
+        assert_eq!(limit.get(), None);
+
+        // a second worth of the rate goes through right away, the next chunk overdraws it
+        let start = Instant::now();
+        limit.acquire(1_000_000).await;
+        limit.acquire(200_000).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        // then the global limit applies
+        let start = Instant::now();
+        limit.acquire(1).await;
+        assert!(start.elapsed() >= Duration::from_millis(150));
+
+        // lifted while an acquire waits, a second worth of the rate is overdrawn
+        limit.acquire(1_000_000).await;
+        let start = Instant::now();
+        let waiting = tokio::spawn({
+            let limit = limit.clone();
+            async move { limit.acquire(1).await }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!waiting.is_finished());
+        global.set(None);
+        waiting.await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_transient_status() {
+        assert!(is_transient_status(StatusCode::BAD_GATEWAY));
+        assert!(is_transient_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_transient_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_transient_status(StatusCode::NOT_IMPLEMENTED));
+        assert!(!is_transient_status(StatusCode::NOT_FOUND));
+        assert!(!is_transient_status(StatusCode::OK));
+    }
+}
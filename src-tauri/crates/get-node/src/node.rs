@@ -1,240 +1,115 @@
-use core::arch;
-
-use cfg_if::cfg_if;
 use node_semver::Version;
 
-cfg_if! {
-  if #[cfg(all(target_os = "windows", target_arch = "x86"))] {
-      /// The OS component of a Node distro filename
-      pub const NODE_DISTRO_OS: &str = "win";
-      /// The architecture component of a Node distro filename
-      pub const NODE_DISTRO_ARCH: &str = "x86";
-      /// The extension for Node distro files
-      pub const NODE_DISTRO_EXTENSION: &str = "zip";
-      /// The file identifier in the Node index `files` array
-      pub const NODE_DISTRO_IDENTIFIER: &str = "win-x86-zip";
-  } else if #[cfg(all(target_os = "windows", target_arch = "x86_64"))] {
-      /// The OS component of a Node distro filename
-      pub const NODE_DISTRO_OS: &str = "win";
-      /// The architecture component of a Node distro filename
-      pub const NODE_DISTRO_ARCH: &str = "x64";
-      /// The extension for Node distro files
-      pub const NODE_DISTRO_EXTENSION: &str = "zip";
-      /// The file identifier in the Node index `files` array
-      pub const NODE_DISTRO_IDENTIFIER: &str = "win-x64-zip";
-  } else if #[cfg(all(target_os = "windows", target_arch = "aarch64"))] {
-      /// The OS component of a Node distro filename
-      pub const NODE_DISTRO_OS: &str = "win";
-      /// The architecture component of a Node distro filename
-      pub const NODE_DISTRO_ARCH: &str = "arm64";
-      /// The extension for Node distro files
-      pub const NODE_DISTRO_EXTENSION: &str = "zip";
-      /// The file identifier in the Node index `files` array
-      pub const NODE_DISTRO_IDENTIFIER: &str = "win-arm64-zip";
-
-      // NOTE: Node support for pre-built ARM64 binaries on Windows was added in major version 20
-      // For versions prior to that, we need to fall back on the x64 binaries via emulator
-
-      /// The fallback architecture component of a Node distro filename
-      pub const NODE_DISTRO_ARCH_FALLBACK: &str = "x64";
-      /// The fallback file identifier in the Node index `files` array
-      pub const NODE_DISTRO_IDENTIFIER_FALLBACK: &str = "win-x64-zip";
-  } else if #[cfg(all(target_os = "macos", target_arch = "x86_64"))] {
-      /// The OS component of a Node distro filename
-      pub const NODE_DISTRO_OS: &str = "darwin";
-      /// The architecture component of a Node distro filename
-      pub const NODE_DISTRO_ARCH: &str = "x64";
-      /// The extension for Node distro files
-      pub const NODE_DISTRO_EXTENSION: &str = "tar.gz";
-      /// The file identifier in the Node index `files` array
-      pub const NODE_DISTRO_IDENTIFIER: &str = "osx-x64-tar";
-  } else if #[cfg(all(target_os = "macos", target_arch = "aarch64"))] {
-      /// The OS component of a Node distro filename
-      pub const NODE_DISTRO_OS: &str = "darwin";
-      /// The architecture component of a Node distro filename
-      pub const NODE_DISTRO_ARCH: &str = "arm64";
-      /// The extension for Node distro files
-      pub const NODE_DISTRO_EXTENSION: &str = "tar.gz";
-      /// The file identifier in the Node index `files` array
-      pub const NODE_DISTRO_IDENTIFIER: &str = "osx-arm64-tar";
-
-      // NOTE: Node support for pre-built Apple Silicon binaries was added in major version 16
-      // For versions prior to that, we need to fall back on the x64 binaries via Rosetta 2
-
-      /// The fallback architecture component of a Node distro filename
-      pub const NODE_DISTRO_ARCH_FALLBACK: &str = "x64";
-      /// The fallback file identifier in the Node index `files` array
-      pub const NODE_DISTRO_IDENTIFIER_FALLBACK: &str = "osx-x64-tar";
-  } else if #[cfg(all(target_os = "linux", target_arch = "x86_64"))] {
-      /// The OS component of a Node distro filename
-      pub const NODE_DISTRO_OS: &str = "linux";
-      /// The architecture component of a Node distro filename
-      pub const NODE_DISTRO_ARCH: &str = "x64";
-      /// The extension for Node distro files
-      pub const NODE_DISTRO_EXTENSION: &str = "tar.gz";
-      /// The file identifier in the Node index `files` array
-      pub const NODE_DISTRO_IDENTIFIER: &str = "linux-x64";
-  } else if #[cfg(all(target_os = "linux", target_arch = "aarch64"))] {
-      /// The OS component of a Node distro filename
-      pub const NODE_DISTRO_OS: &str = "linux";
-      /// The architecture component of a Node distro filename
-      pub const NODE_DISTRO_ARCH: &str = "arm64";
-      /// The extension for Node distro files
-      pub const NODE_DISTRO_EXTENSION: &str = "tar.gz";
-      /// The file identifier in the Node index `files` array
-      pub const NODE_DISTRO_IDENTIFIER: &str = "linux-arm64";
-  } else if #[cfg(all(target_os = "linux", target_arch = "arm"))] {
-      /// The OS component of a Node distro filename
-      pub const NODE_DISTRO_OS: &str = "linux";
-      /// The architecture component of a Node distro filename
-      pub const NODE_DISTRO_ARCH: &str = "armv7l";
-      /// The extension for Node distro files
-      pub const NODE_DISTRO_EXTENSION: &str = "tar.gz";
-      /// The file identifier in the Node index `files` array
-      pub const NODE_DISTRO_IDENTIFIER: &str = "linux-armv7l";
-  } else {
-      compile_error!("Unsuppored operating system + architecture combination");
-  }
-}
+use super::platform::Platform;
 
 /// The Tool implementation for fetching and installing Node
-pub struct Node {
-    pub(super) version: Version,
-}
+pub struct Node;
 
 impl Node {
-    pub fn new(version: Version) -> Self {
-        Node { version }
+    /// The top level directory inside the archive, e.g. `node-v20.2.3-darwin-arm64`
+    pub fn archive_basename(version: &Version, platform: &Platform) -> String {
+        format!(
+            "node-v{}-{}-{}",
+            version,
+            platform.os.distro_name(),
+            platform.archive_arch(version)
+        )
     }
 
-    #[cfg(not(any(
-        all(target_os = "macos", target_arch = "aarch64"),
-        all(target_os = "windows", target_arch = "aarch64")
-    )))]
-    pub fn archive_basename(version: &Version, arch: Option<String>) -> String {
-        let arch = match arch {
-            Some(arch) => arch,
-            None => NODE_DISTRO_ARCH.to_string(),
-        };
-        format!("node-v{}-{}-{}", version, NODE_DISTRO_OS, arch)
-    }
-
-    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-    pub fn archive_basename(version: &Version, arch: Option<String>) -> String {
-        // Note: Node began shipping pre-built binaries for Apple Silicon with Major version 16
-        // Prior to that, we need to fall back on the x64 binaries
-        let arch = match arch {
-            Some(arch) => arch,
-            None => {
-                if version.major >= 16 {
-                    NODE_DISTRO_ARCH
-                } else {
-                    NODE_DISTRO_ARCH_FALLBACK
-                }
-            }
-            .to_string(),
-        };
-        format!("node-v{}-{}-{}", version, NODE_DISTRO_OS, arch)
+    /// The preferred archive filename of the version
+    pub fn archive_filename(version: &Version, platform: &Platform) -> (String, String) {
+        let name = Node::archive_basename(version, platform);
+        let extension = platform.extensions()[0];
+        let full_name = format!("{}.{}", name, extension.as_str());
+        (name, full_name)
     }
 
-    #[cfg(all(target_os = "windows", target_arch = "aarch64"))]
-    pub fn archive_basename(version: &Version, arch: Option<String>) -> String {
-        // Note: Node began shipping pre-built binaries for Windows ARM with Major version 20
-        // Prior to that, we need to fall back on the x64 binaries
-        let arch = match arch {
-            Some(arch) => arch,
-            None => {
-                if version.major >= 20 {
-                    NODE_DISTRO_ARCH
-                } else {
-                    NODE_DISTRO_ARCH_FALLBACK
-                }
-            }
-            .to_string(),
-        };
-        format!("node-v{}-{}-{}", version, NODE_DISTRO_OS, arch)
+    /// The file identifier of the archive in the Node index `files` array, e.g. `osx-arm64-tar`
+    pub fn file_identifier(version: &Version, platform: &Platform) -> String {
+        platform.file_identifier(version)
     }
 
-    pub fn archive_filename(version: &Version, arch: Option<String>) -> (String, String) {
-        let name = Node::archive_basename(version, arch);
-        let full_name = format!("{}.{}", name, NODE_DISTRO_EXTENSION);
-        (name, full_name)
+    /// All the archive filenames of the version in order of preference,
+    /// the much smaller `tar.xz` archives come first on Unix
+    pub fn archive_filenames(version: &Version, platform: &Platform) -> (String, Vec<String>) {
+        let name = Node::archive_basename(version, platform);
+        let full_names = platform
+            .extensions()
+            .iter()
+            .map(|extension| format!("{}.{}", name, extension.as_str()))
+            .collect();
+        (name, full_names)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::platform::{Libc, Os};
 
     #[test]
     fn test_node_archive_basename() {
+        let version = Version::parse("20.2.3").unwrap();
         assert_eq!(
-            Node::archive_basename(&Version::parse("20.2.3").unwrap(), None),
-            format!("node-v20.2.3-{}-{}", NODE_DISTRO_OS, NODE_DISTRO_ARCH)
+            Node::archive_basename(&version, &Platform::new(Os::Linux, "x64")),
+            "node-v20.2.3-linux-x64"
+        );
+        let musl = Platform {
+            libc: Some(Libc::Musl),
+            ..Platform::new(Os::Linux, "arm64")
+        };
+        assert_eq!(
+            Node::archive_basename(&version, &musl),
+            "node-v20.2.3-linux-arm64-musl"
         );
     }
 
     #[test]
     fn test_node_archive_filename() {
-        let (_, full_name) = Node::archive_filename(&Version::parse("20.2.3").unwrap(), None);
-        assert_eq!(
-            full_name,
-            format!(
-                "node-v20.2.3-{}-{}.{}",
-                NODE_DISTRO_OS, NODE_DISTRO_ARCH, NODE_DISTRO_EXTENSION
-            )
-        );
+        let version = Version::parse("20.2.3").unwrap();
+        let (_, full_name) = Node::archive_filename(&version, &Platform::new(Os::Windows, "x64"));
+        assert_eq!(full_name, "node-v20.2.3-win-x64.zip");
+
+        let (_, full_name) = Node::archive_filename(&version, &Platform::new(Os::Darwin, "arm64"));
+        assert_eq!(full_name, "node-v20.2.3-darwin-arm64.tar.xz");
     }
 
     #[test]
-    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-    fn test_fallback_node_archive_basename() {
-        let (_, full_name) = Node::archive_filename(&Version::parse("20.2.3").unwrap(), None);
+    fn test_node_archive_filenames() {
+        let version = Version::parse("20.2.3").unwrap();
+        let (name, full_names) =
+            Node::archive_filenames(&version, &Platform::new(Os::Linux, "x64"));
+        assert_eq!(name, "node-v20.2.3-linux-x64");
         assert_eq!(
-            full_name,
-            format!(
-                "node-v15.2.3-{}-{}",
-                NODE_DISTRO_OS, NODE_DISTRO_ARCH_FALLBACK
-            )
+            full_names,
+            vec![
+                "node-v20.2.3-linux-x64.tar.xz",
+                "node-v20.2.3-linux-x64.tar.gz"
+            ]
         );
     }
 
     #[test]
-    #[cfg(all(target_os = "windows", target_arch = "aarch64"))]
-    fn test_fallback_node_archive_basename() {
-        let (_, full_name) = Node::archive_filename(&Version::parse("20.2.3").unwrap(), None);
+    fn test_node_file_identifier() {
+        let version = Version::parse("20.2.3").unwrap();
         assert_eq!(
-            full_name,
-            format!(
-                "node-v19.2.3-{}-{}",
-                NODE_DISTRO_OS, NODE_DISTRO_ARCH_FALLBACK
-            )
+            Node::file_identifier(&version, &Platform::new(Os::Linux, "s390x")),
+            "linux-s390x"
         );
-    }
-
-    #[test]
-    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-    fn test_fallback_node_archive_filename() {
-        let (_, full_name) = Node::archive_filename(&Version::parse("20.2.3").unwrap(), None);
         assert_eq!(
-            full_name,
-            format!(
-                "node-v15.2.3-{}-{}.{}",
-                NODE_DISTRO_OS, NODE_DISTRO_ARCH_FALLBACK, NODE_DISTRO_EXTENSION
-            )
+            Node::file_identifier(&version, &Platform::new(Os::Darwin, "x64")),
+            "osx-x64-tar"
         );
     }
 
     #[test]
-    #[cfg(all(target_os = "windows", target_arch = "aarch64"))]
     fn test_fallback_node_archive_filename() {
-        let (_, full_name) = Node::archive_filename(&Version::parse("20.2.3").unwrap(), None);
-        assert_eq!(
-            full_name,
-            format!(
-                "node-v19.2.3-{}-{}.{}",
-                NODE_DISTRO_OS, NODE_DISTRO_ARCH_FALLBACK, NODE_DISTRO_EXTENSION
-            )
-        );
+        let darwin = Platform::new(Os::Darwin, "arm64");
+        let (_, full_name) = Node::archive_filename(&Version::parse("15.2.3").unwrap(), &darwin);
+        assert_eq!(full_name, "node-v15.2.3-darwin-x64.tar.xz");
+
+        let windows = Platform::new(Os::Windows, "arm64");
+        let (_, full_name) = Node::archive_filename(&Version::parse("19.2.3").unwrap(), &windows);
+        assert_eq!(full_name, "node-v19.2.3-win-x64.zip");
     }
 }
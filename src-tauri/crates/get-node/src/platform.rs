@@ -0,0 +1,317 @@
+use std::{fmt, sync::OnceLock};
+
+use node_semver::Version;
+use serde::{Deserialize, Serialize};
+
+/// The archs only published by the unofficial builds project
+const UNOFFICIAL_ARCHS: &[&str] = &["riscv64", "loong64"];
+
+/// The oldest glibc the official Linux builds of Node 18+ run on
+const MIN_GLIBC: (u32, u32) = (2, 28);
+
+/// The operating systems Node is published for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Os {
+    Linux,
+    Darwin,
+    #[serde(rename = "win")]
+    Windows,
+    Aix,
+}
+
+impl Os {
+    /// The OS component of a Node distro filename, e.g. `darwin`
+    pub fn distro_name(&self) -> &'static str {
+        match self {
+            Os::Linux => "linux",
+            Os::Darwin => "darwin",
+            Os::Windows => "win",
+            Os::Aix => "aix",
+        }
+    }
+
+    /// The OS component of the Node index `files` array, e.g. `osx`
+    pub fn index_name(&self) -> &'static str {
+        match self {
+            Os::Darwin => "osx",
+            os => os.distro_name(),
+        }
+    }
+}
+
+/// The C library a Linux build is linked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Libc {
+    /// glibc 2.28+, the official builds
+    Glibc,
+
+    /// glibc 2.17, the `x64-glibc-217` unofficial builds (CentOS 7 ...)
+    #[serde(rename = "glibc-217")]
+    Glibc217,
+
+    /// musl, the `-musl` unofficial builds (Alpine ...)
+    Musl,
+}
+
+/// The format of a Node distro archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Ext {
+    #[serde(rename = "tar.xz")]
+    TarXz,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "zip")]
+    Zip,
+}
+
+impl Ext {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Ext::TarXz => "tar.xz",
+            Ext::TarGz => "tar.gz",
+            Ext::Zip => "zip",
+        }
+    }
+}
+
+/// The target platform of a Node distro
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Platform {
+    pub os: Os,
+
+    /// the Node arch name, e.g. `x64`, `arm64`, `armv7l`, `ppc64le`, `riscv64`
+    pub arch: String,
+
+    /// Linux only, `None` for the official builds
+    pub libc: Option<Libc>,
+
+    /// archive format, `None` to pick the best one the mirror lists
+    /// (`tar.xz` then `tar.gz`, `zip` on Windows)
+    pub ext: Option<Ext>,
+}
+
+impl Platform {
+    pub fn new(os: Os, arch: impl Into<String>) -> Self {
+        Self {
+            os,
+            arch: arch.into(),
+            libc: None,
+            ext: None,
+        }
+    }
+
+    /// The platform of the running system, the libc is detected at runtime
+    pub fn current() -> Self {
+        static CURRENT: OnceLock<Platform> = OnceLock::new();
+        CURRENT
+            .get_or_init(|| {
+                let os = match std::env::consts::OS {
+                    "macos" => Os::Darwin,
+                    "windows" => Os::Windows,
+                    "aix" => Os::Aix,
+                    // Node publishes no builds for the BSDs & others, Linux is the closest
+                    _ => Os::Linux,
+                };
+                let arch = match std::env::consts::ARCH {
+                    "x86_64" => "x64",
+                    "x86" => "x86",
+                    "aarch64" => "arm64",
+                    "arm" => "armv7l",
+                    "powerpc64" if cfg!(target_endian = "little") => "ppc64le",
+                    "powerpc64" => "ppc64",
+                    "loongarch64" => "loong64",
+                    arch => arch,
+                };
+                Platform {
+                    libc: (os == Os::Linux).then(detect_libc).flatten(),
+                    ..Platform::new(os, arch)
+                }
+            })
+            .clone()
+    }
+
+    /// The same platform with another arch, e.g. the `x64` builds on Apple Silicon
+    pub fn with_arch(self, arch: Option<String>) -> Self {
+        match arch {
+            Some(arch) => Platform { arch, ..self },
+            None => self,
+        }
+    }
+
+    /// The archive formats in order of preference,
+    /// the much smaller `tar.xz` archives come first on Unix
+    pub fn extensions(&self) -> Vec<Ext> {
+        match (self.ext, self.os) {
+            (Some(ext), _) => vec![ext],
+            (None, Os::Windows) => vec![Ext::Zip],
+            (None, _) => vec![Ext::TarXz, Ext::TarGz],
+        }
+    }
+
+    /// The arch component of the distro filename of `version`
+    /// with the fallbacks for old versions & the unofficial flavours, e.g. `x64-musl`
+    pub fn archive_arch(&self, version: &Version) -> String {
+        let arch = match (self.os, self.arch.as_str()) {
+            // Node began shipping pre-built binaries for Apple Silicon with major version 16
+            (Os::Darwin, "arm64") if version.major < 16 => "x64",
+            // and for Windows ARM with major version 20
+            (Os::Windows, "arm64") if version.major < 20 => "x64",
+            (_, arch) => arch,
+        };
+
+        // already a flavour, e.g. `x64-musl`
+        if arch.contains('-') {
+            return arch.to_string();
+        }
+        match self.libc {
+            Some(Libc::Musl) => format!("{}-musl", arch),
+            Some(Libc::Glibc217) if arch == "x64" && version.major >= 18 => {
+                "x64-glibc-217".to_string()
+            }
+            _ => arch.to_string(),
+        }
+    }
+
+    /// The file identifier of the distro in the Node index `files` array, e.g. `osx-arm64-tar`
+    pub fn file_identifier(&self, version: &Version) -> String {
+        let identifier = format!("{}-{}", self.os.index_name(), self.archive_arch(version));
+        match self.os {
+            Os::Darwin => format!("{}-tar", identifier),
+            Os::Windows => format!("{}-zip", identifier),
+            _ => identifier,
+        }
+    }
+
+    /// Whether the official builds never run on this platform (musl, riscv64, loong64)
+    pub fn is_unofficial_only(&self) -> bool {
+        self.libc == Some(Libc::Musl)
+            || self.arch.contains('-')
+            || UNOFFICIAL_ARCHS.contains(&self.arch.as_str())
+    }
+
+    /// Whether `version` is only published for this platform by the unofficial builds project
+    pub fn is_unofficial(&self, version: &Version) -> bool {
+        self.is_unofficial_only() || self.archive_arch(version).contains('-')
+    }
+}
+
+impl Default for Platform {
+    fn default() -> Self {
+        Platform::current()
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.os.distro_name(), self.arch)?;
+        match self.libc {
+            Some(Libc::Musl) => write!(f, "-musl"),
+            Some(Libc::Glibc217) => write!(f, "-glibc-217"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Detect the C library of the running Linux system,
+/// `None` when it is not Linux or unknown
+pub fn detect_libc() -> Option<Libc> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    if let Some(glibc) = glibc_version() {
+        return Some(if glibc < MIN_GLIBC {
+            Libc::Glibc217
+        } else {
+            Libc::Glibc
+        });
+    }
+
+    // the musl dynamic loader is `/lib/ld-musl-<arch>.so.1`
+    let musl = std::fs::read_dir("/lib").is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|entry| entry.file_name().to_string_lossy().starts_with("ld-musl-"))
+    });
+    musl.then_some(Libc::Musl)
+}
+
+/// The glibc version from `getconf GNU_LIBC_VERSION` (e.g. `glibc 2.31`)
+fn glibc_version() -> Option<(u32, u32)> {
+    let output = std::process::Command::new("getconf")
+        .arg("GNU_LIBC_VERSION")
+        .output()
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    let mut parts = output.trim().strip_prefix("glibc ")?.split('.');
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn test_archive_arch() {
+        let darwin = Platform::new(Os::Darwin, "arm64");
+        assert_eq!(darwin.archive_arch(&version("20.2.3")), "arm64");
+        assert_eq!(darwin.archive_arch(&version("15.2.3")), "x64");
+
+        let windows = Platform::new(Os::Windows, "arm64");
+        assert_eq!(windows.archive_arch(&version("19.2.3")), "x64");
+
+        let musl = Platform {
+            libc: Some(Libc::Musl),
+            ..Platform::new(Os::Linux, "x64")
+        };
+        assert_eq!(musl.archive_arch(&version("20.2.3")), "x64-musl");
+        assert!(musl.is_unofficial_only());
+
+        let centos = Platform {
+            libc: Some(Libc::Glibc217),
+            ..Platform::new(Os::Linux, "x64")
+        };
+        assert_eq!(centos.archive_arch(&version("20.2.3")), "x64-glibc-217");
+        assert!(centos.is_unofficial(&version("20.2.3")));
+        assert!(!centos.is_unofficial(&version("16.2.3")));
+    }
+
+    #[test]
+    fn test_file_identifier() {
+        let version = version("20.2.3");
+        assert_eq!(
+            Platform::new(Os::Darwin, "arm64").file_identifier(&version),
+            "osx-arm64-tar"
+        );
+        assert_eq!(
+            Platform::new(Os::Windows, "x64").file_identifier(&version),
+            "win-x64-zip"
+        );
+        assert_eq!(
+            Platform::new(Os::Linux, "riscv64").file_identifier(&version),
+            "linux-riscv64"
+        );
+    }
+
+    #[test]
+    fn test_extensions() {
+        assert_eq!(
+            Platform::new(Os::Linux, "x64").extensions(),
+            vec![Ext::TarXz, Ext::TarGz]
+        );
+        assert_eq!(
+            Platform::new(Os::Windows, "x64").extensions(),
+            vec![Ext::Zip]
+        );
+        let platform = Platform {
+            ext: Some(Ext::TarGz),
+            ..Platform::new(Os::Darwin, "x64")
+        };
+        assert_eq!(platform.extensions(), vec![Ext::TarGz]);
+    }
+}
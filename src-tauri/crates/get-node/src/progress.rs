@@ -0,0 +1,177 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use futures_util::Stream;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+/// get progress events, in the order of the phases of a fetch
+pub type OnProgress = dyn Fn(&Progress) + Send + Sync;
+
+/// A progress event of a fetch
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "phase", rename_all = "lowercase")]
+pub enum Progress {
+    /// looking up the archive & its checksum on the mirror at `mirror`
+    Resolving { mirror: String },
+
+    /// requesting the archive
+    Connecting { url: String },
+
+    /// downloading the archive, in bytes
+    Downloading(Transfer),
+
+    /// checking the sha256 of the archive
+    Verifying,
+
+    /// unpacking the archive, in compressed bytes for a tarball & in entries for a zip
+    Extracting(Transfer),
+
+    /// validating the install & moving it into place
+    Finalizing,
+
+    /// retrying a request after a transient failure, in `delay` milliseconds
+    Retrying {
+        attempt: u32,
+        retries: u32,
+        delay: u64,
+    },
+}
+
+/// How far a download or an unpacking is
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Transfer {
+    pub done: u64,
+
+    /// `None` when unknown, e.g. a chunked response
+    pub total: Option<u64>,
+
+    /// per second, averaged since the start
+    pub rate: f64,
+
+    /// seconds left, `None` when the total is unknown or nothing was done yet
+    pub eta: Option<u64>,
+}
+
+impl Transfer {
+    /// The transfer once `done` out of `total` after `elapsed` seconds,
+    /// `offset` being what was done already when it started, e.g. a resumed download
+    pub fn measure(done: u64, total: Option<u64>, offset: u64, elapsed: f64) -> Self {
+        let rate = if elapsed > 0.0 {
+            done.saturating_sub(offset) as f64 / elapsed
+        } else {
+            0.0
+        };
+        let eta = match total {
+            Some(total) if rate > 0.0 => {
+                Some((total.saturating_sub(done) as f64 / rate).ceil() as u64)
+            }
+            _ => None,
+        };
+        Self {
+            done,
+            total,
+            rate,
+            eta,
+        }
+    }
+}
+
+/// Measure a transfer from the time it is created
+pub(crate) struct Meter {
+    started: Instant,
+
+    offset: u64,
+
+    total: Option<u64>,
+}
+
+impl Meter {
+    pub(crate) fn new(offset: u64, total: Option<u64>) -> Self {
+        Self {
+            started: Instant::now(),
+            offset,
+            total,
+        }
+    }
+
+    pub(crate) fn transfer(&self, done: u64) -> Transfer {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        Transfer::measure(done, self.total, self.offset, elapsed)
+    }
+}
+
+/// The progress events as a `Stream`, it ends once the callback feeding it is dropped
+pub struct ProgressStream(mpsc::UnboundedReceiver<Progress>);
+
+impl Stream for ProgressStream {
+    type Item = Progress;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Progress>> {
+        self.0.poll_recv(cx)
+    }
+}
+
+/// Subscribe to the progress events reported to `on_progress`
+/// return the callback to fetch with, it still calls `on_progress` first
+pub fn subscribe(on_progress: Box<OnProgress>) -> (Box<OnProgress>, ProgressStream) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let on_progress: Box<OnProgress> = Box::new(move |progress: &Progress| {
+        on_progress(progress);
+        // nobody listens anymore, the fetch goes on
+        let _ = sender.send(progress.clone());
+    });
+    (on_progress, ProgressStream(receiver))
+}
+
+/// The progress events as a `Stream` only, with the callback to fetch with
+pub fn channel() -> (Box<OnProgress>, ProgressStream) {
+    subscribe(Box::new(|_: &Progress| {}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    #[test]
+    fn test_measure() {
+        let transfer = Transfer::measure(3_000, Some(10_000), 1_000, 2.0);
+        assert_eq!(transfer.rate, 1_000.0);
+        assert_eq!(transfer.eta, Some(7));
+
+        // chunked response
+        let transfer = Transfer::measure(3_000, None, 0, 2.0);
+        assert_eq!(transfer.rate, 1_500.0);
+        assert_eq!(transfer.eta, None);
+
+        assert_eq!(Transfer::measure(0, Some(10), 0, 0.0).eta, None);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let progress = Progress::Downloading(Transfer::measure(5, Some(10), 0, 1.0));
+        assert_eq!(
+            serde_json::to_value(&progress).unwrap(),
+            serde_json::json!({ "phase": "downloading", "done": 5, "total": 10, "rate": 5.0, "eta": 1 })
+        );
+        assert_eq!(
+            serde_json::to_value(Progress::Verifying).unwrap(),
+            serde_json::json!({ "phase": "verifying" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let (on_progress, stream) = channel();
+        on_progress(&Progress::Verifying);
+        on_progress(&Progress::Finalizing);
+        drop(on_progress);
+
+        let events: Vec<Progress> = stream.collect().await;
+        assert_eq!(events, vec![Progress::Verifying, Progress::Finalizing]);
+    }
+}
@@ -0,0 +1,462 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::Url;
+
+/// How long a loaded PAC file is reused before it is fetched again
+const PAC_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// How long `FindProxyForURL` may run for a host, DNS lookups included, before connecting directly
+const PAC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The helper functions of the PAC files, as defined by Netscape
+///
+/// The DNS lookups are done beforehand, `__dns` holds the address of the host
+/// & `__myIp` the address of this machine.
+#[cfg(feature = "pac")]
+const PAC_UTILS: &str = r#"
+var __days = ['SUN', 'MON', 'TUE', 'WED', 'THU', 'FRI', 'SAT'];
+var __months = ['JAN', 'FEB', 'MAR', 'APR', 'MAY', 'JUN', 'JUL', 'AUG', 'SEP', 'OCT', 'NOV', 'DEC'];
+function isPlainHostName(host) { return host.indexOf('.') < 0; }
+function dnsDomainIs(host, domain) {
+  return host.length >= domain.length && host.substring(host.length - domain.length) === domain;
+}
+function localHostOrDomainIs(host, hostdom) {
+  return host === hostdom || hostdom.lastIndexOf(host + '.', 0) === 0;
+}
+function dnsResolve(host) { return __dns.hasOwnProperty(host) ? __dns[host] : null; }
+function isResolvable(host) { return dnsResolve(host) !== null; }
+function myIpAddress() { return __myIp; }
+function dnsDomainLevels(host) { return host.split('.').length - 1; }
+function __ip(ip) {
+  var parts = String(ip).split('.');
+  if (parts.length !== 4) return null;
+  var n = 0;
+  for (var i = 0; i < 4; i++) {
+    var b = parseInt(parts[i], 10);
+    if (isNaN(b) || b < 0 || b > 255) return null;
+    n = n * 256 + b;
+  }
+  return n;
+}
+function isInNet(host, pattern, mask) {
+  var ip = __ip(dnsResolve(host) || host), p = __ip(pattern), m = __ip(mask);
+  if (ip === null || p === null || m === null) return false;
+  return ((ip & m) >>> 0) === ((p & m) >>> 0);
+}
+function shExpMatch(str, shexp) {
+  var re = shexp.replace(/[.+^${}()|[\]\\]/g, '\\$&').replace(/\*/g, '.*').replace(/\?/g, '.');
+  return new RegExp('^' + re + '$').test(str);
+}
+function __args(args) {
+  var list = Array.prototype.slice.call(args);
+  var gmt = list[list.length - 1] === 'GMT';
+  if (gmt) list.pop();
+  var d = new Date();
+  return {
+    list: list,
+    now: {
+      y: gmt ? d.getUTCFullYear() : d.getFullYear(), m: gmt ? d.getUTCMonth() : d.getMonth(),
+      d: gmt ? d.getUTCDate() : d.getDate(), day: gmt ? d.getUTCDay() : d.getDay(),
+      s: (gmt ? d.getUTCHours() : d.getHours()) * 3600 + (gmt ? d.getUTCMinutes() : d.getMinutes()) * 60
+        + (gmt ? d.getUTCSeconds() : d.getSeconds())
+    }
+  };
+}
+function __within(n, a, b) { return a <= b ? n >= a && n <= b : n >= a || n <= b; }
+function weekdayRange() {
+  var args = __args(arguments), a = __days.indexOf(args.list[0]);
+  if (args.list.length < 2) return args.now.day === a;
+  return __within(args.now.day, a, __days.indexOf(args.list[1]));
+}
+function timeRange() {
+  var args = __args(arguments), l = args.list, now = args.now.s;
+  if (l.length === 1) return Math.floor(now / 3600) === l[0];
+  if (l.length === 2) return __within(now, l[0] * 3600, l[1] * 3600);
+  if (l.length === 4) return __within(now, l[0] * 3600 + l[1] * 60, l[2] * 3600 + l[3] * 60);
+  if (l.length === 6) {
+    return __within(now, l[0] * 3600 + l[1] * 60 + l[2], l[3] * 3600 + l[4] * 60 + l[5]);
+  }
+  return false;
+}
+function dateRange() {
+  var args = __args(arguments), now = args.now;
+  var parts = args.list.map(function (a) {
+    if (typeof a === 'string') return { m: __months.indexOf(a) };
+    return a > 31 ? { y: a } : { d: a };
+  });
+  function merge(list) {
+    var r = {};
+    list.forEach(function (p) { for (var k in p) r[k] = p[k]; });
+    return r;
+  }
+  var half = parts.length === 1 ? 1 : parts.length / 2;
+  var from = merge(parts.slice(0, half)), to = parts.length === 1 ? from : merge(parts.slice(half));
+  var keys = ['y', 'm', 'd'].filter(function (k) { return from[k] !== undefined; });
+  function value(p) {
+    return keys.reduce(function (v, k) { return v * 100 + (k === 'y' ? p[k] - 1900 : p[k]); }, 0);
+  }
+  return __within(value(now), value(from), value(to));
+}
+"#;
+
+/// The PAC files loaded by location, with when they were fetched
+type Loaded = Mutex<HashMap<String, (Instant, Arc<Pac>)>>;
+
+/// A proxy auto-config file
+#[derive(Debug)]
+pub(crate) struct Pac {
+    script: String,
+
+    /// the proxies already found, by `scheme://host:port/`
+    /// the requests only use these, the PAC file is never run while connecting
+    found: Mutex<HashMap<String, Option<Url>>>,
+}
+
+impl Pac {
+    pub(crate) fn new(script: String) -> Pac {
+        Pac {
+            script,
+            found: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Load the PAC file from a url (`http://`, `https://` or `file://`) or a path
+    /// it is checked to define `FindProxyForURL`
+    pub(crate) async fn load(location: &str) -> Result<Arc<Pac>> {
+        static LOADED: OnceLock<Loaded> = OnceLock::new();
+        let loaded = LOADED.get_or_init(|| Mutex::new(HashMap::new()));
+
+        if let Some((at, pac)) = loaded
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .get(location)
+        {
+            if at.elapsed() < PAC_TTL {
+                return Ok(pac.clone());
+            }
+        }
+
+        let pac = Pac::new(fetch_script(location).await?);
+        let defined = evaluate(&pac.script, "typeof FindProxyForURL", "")?;
+        if defined != "function" {
+            bail!(
+                "Invalid PAC file \"{}\", FindProxyForURL is not defined",
+                location
+            );
+        }
+
+        let pac = Arc::new(pac);
+        loaded
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(location.to_string(), (Instant::now(), pac.clone()));
+        Ok(pac)
+    }
+
+    /// The proxy returned by `FindProxyForURL` for `url`, `None` for `DIRECT`
+    ///
+    /// Like browsers do for `https://` urls, only `scheme://host:port/` is passed to the PAC file,
+    /// so the proxy is the same for every url of a host.
+    /// It blocks while the PAC file runs, see `lookup` on the runtime.
+    pub(crate) fn find(&self, url: &Url) -> Result<Option<Url>> {
+        if let Some(proxy) = self.cached(url) {
+            return Ok(proxy);
+        }
+
+        let (stripped, host) = target(url)?;
+        let call = format!(
+            "FindProxyForURL({}, {})",
+            serde_json::to_string(&stripped)?,
+            serde_json::to_string(host)?
+        );
+        // run without the lock, a slow host does not hold up the others
+        let proxy = parse_result(&evaluate(&self.script, &call, host)?)?;
+        self.found
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(stripped, proxy.clone());
+        Ok(proxy)
+    }
+
+    /// `find` on a blocking thread, an error when it takes longer than `PAC_TIMEOUT`
+    /// the proxy is still cached once the PAC file is done
+    pub(crate) async fn lookup(self: &Arc<Self>, url: &Url) -> Result<Option<Url>> {
+        if let Some(proxy) = self.cached(url) {
+            return Ok(proxy);
+        }
+
+        let pac = self.clone();
+        let target = url.clone();
+        match tokio::time::timeout(
+            PAC_TIMEOUT,
+            tokio::task::spawn_blocking(move || pac.find(&target)),
+        )
+        .await
+        {
+            Ok(ret) => ret?,
+            Err(_) => bail!(
+                "The PAC file took longer than {}s for {}",
+                PAC_TIMEOUT.as_secs(),
+                url.host_str().unwrap_or_default()
+            ),
+        }
+    }
+
+    /// The proxy of `url` without running the PAC file, which would block the runtime
+    ///
+    /// The hosts are looked up beforehand (see `Resolver::prepare`), another one (e.g. a redirect)
+    /// connects directly while it is looked up for the next requests.
+    pub(crate) fn resolve(self: &Arc<Self>, url: &Url) -> Option<Url> {
+        self.cached(url).unwrap_or_else(|| {
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                let (pac, url) = (self.clone(), url.clone());
+                runtime.spawn(async move { pac.lookup(&url).await });
+            }
+            None
+        })
+    }
+
+    /// The proxy found for the host of `url` already, `None` when it was not looked up yet
+    pub(crate) fn cached(&self, url: &Url) -> Option<Option<Url>> {
+        let (stripped, _) = target(url).ok()?;
+        self.found
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .get(&stripped)
+            .cloned()
+    }
+}
+
+/// What is passed to the PAC file for `url`: `scheme://host:port/` & the host
+fn target(url: &Url) -> Result<(String, &str)> {
+    let host = url.host_str().context("The url has no host")?;
+    let stripped = match url.port() {
+        Some(port) => format!("{}://{}:{}/", url.scheme(), host, port),
+        None => format!("{}://{}/", url.scheme(), host),
+    };
+    Ok((stripped, host))
+}
+
+/// Fetch the PAC file content
+async fn fetch_script(location: &str) -> Result<String> {
+    if location.starts_with("http://") || location.starts_with("https://") {
+        // the PAC file is fetched directly, it is the one telling which proxy to use
+        let client = reqwest::ClientBuilder::new()
+            .use_rustls_tls()
+            .no_proxy()
+            .timeout(Duration::from_secs(20))
+            .build()?;
+        let response = client.get(location).send().await?.error_for_status()?;
+        return Ok(response.text().await?);
+    }
+
+    let path = match Url::parse(location) {
+        Ok(url) if url.scheme() == "file" => url
+            .to_file_path()
+            .map_err(|_| anyhow!("Invalid PAC file path \"{}\"", location))?,
+        _ => PathBuf::from(location),
+    };
+    tokio::fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("Failed to read the PAC file {:?}", path))
+}
+
+/// The first supported proxy of a `FindProxyForURL` result,
+/// e.g. `PROXY proxy.corp:8080; SOCKS5 proxy.corp:1080; DIRECT`
+fn parse_result(ret: &str) -> Result<Option<Url>> {
+    let entries: Vec<&str> = ret
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .collect();
+    if entries.is_empty() || ret == "null" || ret == "undefined" {
+        return Ok(None);
+    }
+
+    for entry in &entries {
+        let mut parts = entry.split_whitespace();
+        let scheme = match parts
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase()
+            .as_str()
+        {
+            "DIRECT" => return Ok(None),
+            "PROXY" | "HTTP" => "http",
+            "HTTPS" => "https",
+            "SOCKS" | "SOCKS5" => "socks5",
+            // SOCKS4 is not supported, try the next one
+            _ => continue,
+        };
+        if let Some(Ok(url)) = parts
+            .next()
+            .map(|address| Url::parse(&format!("{}://{}", scheme, address)))
+        {
+            return Ok(Some(url));
+        }
+    }
+
+    bail!("No supported proxy in the PAC result \"{}\"", ret)
+}
+
+/// The DNS lookups the PAC file may do, only when it uses them
+#[cfg(feature = "pac")]
+fn lookups(script: &str, host: &str) -> HashMap<String, String> {
+    let mut dns = HashMap::new();
+    let uses_dns = ["dnsResolve", "isResolvable", "isInNet"]
+        .iter()
+        .any(|name| script.contains(name));
+    if !uses_dns || host.is_empty() {
+        return dns;
+    }
+
+    use std::net::ToSocketAddrs;
+
+    if let Some(address) = (host, 0)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.find(|address| address.is_ipv4()))
+    {
+        dns.insert(host.to_string(), address.ip().to_string());
+    }
+    dns
+}
+
+/// The address of this machine on the default route, nothing is sent
+#[cfg(feature = "pac")]
+fn my_ip() -> String {
+    use std::net::UdpSocket;
+
+    UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| {
+            socket.connect("8.8.8.8:80")?;
+            socket.local_addr()
+        })
+        .map(|address| address.ip().to_string())
+        .unwrap_or_else(|_| "127.0.0.1".to_string())
+}
+
+/// Run `script` then `call`, the result of `call` as a string
+#[cfg(feature = "pac")]
+fn evaluate(script: &str, call: &str, host: &str) -> Result<String> {
+    use boa_engine::{Context as JsContext, Source};
+
+    // only when the PAC file uses it
+    let my_ip = match script.contains("myIpAddress") {
+        true => my_ip(),
+        false => "127.0.0.1".to_string(),
+    };
+    let prelude = format!(
+        "{}\nvar __dns = {};\nvar __myIp = {};\n",
+        PAC_UTILS,
+        serde_json::to_string(&lookups(script, host))?,
+        serde_json::to_string(&my_ip)?
+    );
+
+    let mut context = JsContext::default();
+    context
+        .eval(Source::from_bytes(&prelude))
+        .map_err(|err| anyhow!("Failed to define the PAC functions: {}", err))?;
+    context
+        .eval(Source::from_bytes(script))
+        .map_err(|err| anyhow!("Invalid PAC file: {}", err))?;
+    let value = context
+        .eval(Source::from_bytes(call))
+        .map_err(|err| anyhow!("Failed to run the PAC file: {}", err))?;
+
+    Ok(value
+        .to_string(&mut context)
+        .map_err(|err| anyhow!("Invalid PAC result: {}", err))?
+        .to_std_string_escaped())
+}
+
+#[cfg(not(feature = "pac"))]
+fn evaluate(_script: &str, _call: &str, _host: &str) -> Result<String> {
+    bail!("PAC files are not supported, get-node is built without the `pac` feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_result() {
+        assert_eq!(parse_result("DIRECT").unwrap(), None);
+        assert_eq!(
+            parse_result("SOCKS4 old.corp:1080; PROXY proxy.corp:8080; DIRECT")
+                .unwrap()
+                .unwrap()
+                .as_str(),
+            "http://proxy.corp:8080/"
+        );
+        assert_eq!(
+            parse_result("SOCKS5 proxy.corp:1080")
+                .unwrap()
+                .unwrap()
+                .as_str(),
+            "socks5://proxy.corp:1080"
+        );
+        assert!(parse_result("SOCKS4 old.corp:1080").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "pac")]
+    fn test_find() {
+        let pac = Pac::new(
+            r#"function FindProxyForURL(url, host) {
+                if (isPlainHostName(host) || dnsDomainIs(host, ".corp.example.com")) return "DIRECT";
+                if (shExpMatch(url, "https://*.nodejs.org/*")) return "PROXY secure.corp:8443";
+                return "PROXY proxy.corp:8080; DIRECT";
+            }"#
+            .to_string(),
+        );
+        let find = |url: &str| pac.find(&Url::parse(url).unwrap()).unwrap();
+
+        assert_eq!(find("http://intranet/dist"), None);
+        assert_eq!(find("https://npm.corp.example.com/"), None);
+        assert_eq!(
+            find("https://www.nodejs.org/dist/index.json")
+                .unwrap()
+                .as_str(),
+            "http://secure.corp:8443/"
+        );
+        assert_eq!(
+            find("https://registry.npmmirror.com/").unwrap().as_str(),
+            "http://proxy.corp:8080/"
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "pac")]
+    async fn test_lookup() {
+        let pac = Arc::new(Pac::new(
+            r#"function FindProxyForURL(url, host) { return "PROXY proxy.corp:8080"; }"#
+                .to_string(),
+        ));
+        let resolver = crate::proxy::Resolver::Pac {
+            pac: pac.clone(),
+            bypass: vec![],
+        };
+        let url = Url::parse("https://nodejs.org/dist/index.json").unwrap();
+        assert_eq!(pac.cached(&url), None);
+
+        resolver.prepare(&url).await;
+        assert_eq!(
+            resolver.resolve(&url).unwrap().as_str(),
+            "http://proxy.corp:8080/"
+        );
+        // the proxy is found by host
+        let archive = Url::parse("https://nodejs.org/dist/v20.11.1/node.tar.xz").unwrap();
+        assert!(pac.cached(&archive).is_some());
+
+        // a host not looked up beforehand connects directly, the PAC file is not run meanwhile
+        let other = Url::parse("https://registry.npmmirror.com/").unwrap();
+        assert_eq!(resolver.resolve(&other), None);
+    }
+}
@@ -0,0 +1,403 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    process::Command,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use reqwest::Url;
+
+use super::{bypassed, pac::Pac};
+
+/// How long the detected settings are reused, they rarely change
+const DETECT_TTL: Duration = Duration::from_secs(60);
+
+/// The proxy settings of the system
+#[derive(Debug, Default, Clone)]
+pub(crate) struct SystemProxy {
+    /// proxy of the `http://` urls
+    http: Option<Url>,
+
+    /// proxy of the `https://` urls
+    https: Option<Url>,
+
+    /// proxy of any url, when there is no proxy for its scheme
+    all: Option<Url>,
+
+    /// hosts to connect to directly
+    bypass: Vec<String>,
+
+    /// the url or path of the PAC file, only used when no proxy is set
+    pac_location: Option<String>,
+
+    /// the PAC file loaded from `pac_location`
+    pac: Option<Arc<Pac>>,
+}
+
+impl SystemProxy {
+    /// Detect the settings: the environment first, then the desktop or OS settings
+    pub(crate) async fn detect() -> SystemProxy {
+        static DETECTED: OnceLock<Mutex<Option<(Instant, SystemProxy)>>> = OnceLock::new();
+        let detected = DETECTED.get_or_init(|| Mutex::new(None));
+
+        if let Some((at, proxy)) = detected
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .as_ref()
+        {
+            if at.elapsed() < DETECT_TTL {
+                return proxy.clone();
+            }
+        }
+
+        // the desktop & OS settings are read by running commands
+        let mut proxy = tokio::task::spawn_blocking(|| from_env().or_else(from_os))
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        if let Some(location) = proxy.pac_location.as_deref() {
+            // like browsers, a PAC file that can not be loaded means connecting directly
+            proxy.pac = Pac::load(location).await.ok();
+        }
+
+        *detected.lock().unwrap_or_else(|err| err.into_inner()) =
+            Some((Instant::now(), proxy.clone()));
+        proxy
+    }
+
+    /// The proxy of a request to `url`, `None` to connect directly
+    /// the PAC file is not run here, see `prepare`
+    pub(crate) fn resolve(&self, url: &Url) -> Option<Url> {
+        let host = url.host_str()?;
+        if bypassed(host, &self.bypass) {
+            return None;
+        }
+        if let Some(proxy) = self.proxy_for(url) {
+            return Some(proxy.clone());
+        }
+        self.pac.as_ref()?.resolve(url)
+    }
+
+    /// Look up the proxy of `url` in the PAC file beforehand, when it is the one deciding
+    pub(crate) async fn prepare(&self, url: &Url) {
+        let Some(pac) = self.pac.as_ref() else {
+            return;
+        };
+        if url
+            .host_str()
+            .is_some_and(|host| bypassed(host, &self.bypass))
+            || self.proxy_for(url).is_some()
+        {
+            return;
+        }
+        let _ = pac.lookup(url).await;
+    }
+
+    /// The proxy set for the scheme of `url`, or for any url
+    fn proxy_for(&self, url: &Url) -> Option<&Url> {
+        let proxy = match url.scheme() {
+            "https" => self.https.as_ref(),
+            "http" => self.http.as_ref(),
+            _ => None,
+        };
+        proxy.or(self.all.as_ref())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.http.is_none()
+            && self.https.is_none()
+            && self.all.is_none()
+            && self.pac_location.is_none()
+    }
+}
+
+/// A proxy url, `http://` when the scheme is missing (`proxy.corp:8080`)
+fn proxy_url(value: &str) -> Option<Url> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    match value.contains("://") {
+        true => Url::parse(value).ok(),
+        false => Url::parse(&format!("http://{}", value)).ok(),
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split([',', ';', ' ', '\n'])
+        .map(|entry| entry.trim().to_string())
+        .filter(|entry| !entry.is_empty())
+        .collect()
+}
+
+/// `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` & `NO_PROXY`, lowercase ones first like curl
+fn from_env() -> Option<SystemProxy> {
+    let var = |name: &str| {
+        std::env::var(name.to_ascii_lowercase())
+            .or_else(|_| std::env::var(name))
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+    };
+
+    let proxy = SystemProxy {
+        // `HTTP_PROXY` can be set by a CGI request header, only the lowercase one is trusted
+        http: std::env::var("http_proxy")
+            .ok()
+            .and_then(|value| proxy_url(&value)),
+        https: var("HTTPS_PROXY").and_then(|value| proxy_url(&value)),
+        all: var("ALL_PROXY").and_then(|value| proxy_url(&value)),
+        bypass: var("NO_PROXY")
+            .map(|value| split_list(&value))
+            .unwrap_or_default(),
+        ..SystemProxy::default()
+    };
+    (!proxy.is_empty()).then_some(proxy)
+}
+
+/// The proxy settings of the desktop (GNOME, KDE) or of the OS (Windows, macOS)
+fn from_os() -> Option<SystemProxy> {
+    if cfg!(target_os = "windows") {
+        from_windows()
+    } else if cfg!(target_os = "macos") {
+        from_macos()
+    } else {
+        from_gnome().or_else(from_kde)
+    }
+}
+
+/// Run a command, its stdout when it succeeds
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let mut command = Command::new(program);
+    command.args(args);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+
+        // `CREATE_NO_WINDOW`, no console flashes over the app
+        command.creation_flags(0x08000000);
+    }
+    let output = command.output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// The location of a PAC file, `None` when empty
+fn pac_location(location: &str) -> Option<String> {
+    let location = location.trim();
+    (!location.is_empty()).then(|| location.to_string())
+}
+
+/// `gsettings get org.gnome.system.proxy ...`
+fn from_gnome() -> Option<SystemProxy> {
+    let get = |schema: &str, key: &str| {
+        output("gsettings", &["get", schema, key])
+            .map(|value| value.trim().trim_matches('\'').to_string())
+    };
+
+    match get("org.gnome.system.proxy", "mode")?.as_str() {
+        "auto" => Some(SystemProxy {
+            pac_location: pac_location(&get("org.gnome.system.proxy", "autoconfig-url")?),
+            ..SystemProxy::default()
+        }),
+        "manual" => {
+            let proxy = |schema: &str, scheme: &str| {
+                let host = get(schema, "host").filter(|host| !host.is_empty())?;
+                let port = get(schema, "port").filter(|port| port != "0")?;
+                Url::parse(&format!("{}://{}:{}", scheme, host, port)).ok()
+            };
+            // `['localhost', '127.0.0.0/8']`
+            let bypass = get("org.gnome.system.proxy", "ignore-hosts")
+                .map(|hosts| split_list(&hosts.replace(['[', ']', '\''], " ")))
+                .unwrap_or_default();
+
+            let proxy = SystemProxy {
+                http: proxy("org.gnome.system.proxy.http", "http"),
+                https: proxy("org.gnome.system.proxy.https", "http"),
+                all: proxy("org.gnome.system.proxy.socks", "socks5"),
+                bypass,
+                ..SystemProxy::default()
+            };
+            (!proxy.is_empty()).then_some(proxy)
+        }
+        _ => None,
+    }
+}
+
+/// `[Proxy Settings]` of `~/.config/kioslaverc`
+fn from_kde() -> Option<SystemProxy> {
+    let config_dir = std::env::var("XDG_CONFIG_HOME")
+        .ok()
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var("HOME")
+                .ok()
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    let content = std::fs::read_to_string(config_dir.join("kioslaverc")).ok()?;
+
+    let mut in_section = false;
+    let mut settings = HashMap::new();
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_section = line == "[Proxy Settings]";
+        } else if let (true, Some((key, value))) = (in_section, line.split_once('=')) {
+            settings.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    let get = |key: &str| settings.get(key).map(String::as_str).unwrap_or_default();
+
+    // `http://proxy.corp 8080` or `http://proxy.corp:8080`
+    let proxy = |key: &str| proxy_url(&get(key).replacen(' ', ":", 1));
+    match get("ProxyType") {
+        "1" => {
+            let proxy = SystemProxy {
+                http: proxy("httpProxy"),
+                https: proxy("httpsProxy"),
+                all: proxy("socksProxy").map(|mut url| {
+                    let _ = url.set_scheme("socks5");
+                    url
+                }),
+                bypass: split_list(get("NoProxyFor")),
+                ..SystemProxy::default()
+            };
+            (!proxy.is_empty()).then_some(proxy)
+        }
+        "2" => Some(SystemProxy {
+            pac_location: pac_location(get("Proxy Config Script")),
+            ..SystemProxy::default()
+        }),
+        _ => None,
+    }
+}
+
+/// `HKCU\Software\Microsoft\Windows\CurrentVersion\Internet Settings`
+fn from_windows() -> Option<SystemProxy> {
+    let settings = output(
+        "reg",
+        &[
+            "query",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Internet Settings",
+        ],
+    )?;
+    // `    ProxyServer    REG_SZ    proxy.corp:8080`
+    let get = |name: &str| {
+        settings.lines().find_map(|line| {
+            let mut parts = line.split_whitespace();
+            (parts.next() == Some(name)).then(|| parts.skip(1).collect::<Vec<_>>().join(" "))
+        })
+    };
+
+    let mut proxy = SystemProxy {
+        bypass: get("ProxyOverride")
+            .map(|hosts| split_list(&hosts))
+            .unwrap_or_default(),
+        ..SystemProxy::default()
+    };
+    if get("ProxyEnable").as_deref() == Some("0x1") {
+        // `proxy.corp:8080` or `http=proxy.corp:8080;https=proxy.corp:8443;socks=proxy.corp:1080`
+        for server in get("ProxyServer").unwrap_or_default().split(';') {
+            match server.split_once('=') {
+                Some(("http", value)) => proxy.http = proxy_url(value),
+                Some(("https", value)) => proxy.https = proxy_url(value),
+                Some(("socks", value)) => proxy.all = proxy_url(&format!("socks5://{}", value)),
+                Some(_) => {}
+                None => proxy.all = proxy_url(server),
+            }
+        }
+    }
+    if proxy.is_empty() {
+        proxy.pac_location = get("AutoConfigURL").and_then(|location| pac_location(&location));
+    }
+
+    (!proxy.is_empty()).then_some(proxy)
+}
+
+/// `scutil --proxy`
+fn from_macos() -> Option<SystemProxy> {
+    let settings = output("scutil", &["--proxy"])?;
+    // `  HTTPEnable : 1`
+    let get = |name: &str| {
+        settings.lines().find_map(|line| {
+            let (key, value) = line.split_once(" : ")?;
+            (key.trim() == name).then(|| value.trim().to_string())
+        })
+    };
+    let proxy = |kind: &str, scheme: &str| {
+        if get(&format!("{}Enable", kind)).as_deref() != Some("1") {
+            return None;
+        }
+        let host = get(&format!("{}Proxy", kind))?;
+        let port = get(&format!("{}Port", kind))?;
+        Url::parse(&format!("{}://{}:{}", scheme, host, port)).ok()
+    };
+
+    // `    0 : *.local` in the `ExceptionsList : <array> { ... }` block
+    let bypass = settings
+        .lines()
+        .skip_while(|line| !line.contains("ExceptionsList"))
+        .skip(1)
+        .take_while(|line| !line.contains('}'))
+        .filter_map(|line| {
+            line.split_once(" : ")
+                .map(|(_, host)| host.trim().to_string())
+        })
+        .collect();
+
+    let mut proxy = SystemProxy {
+        http: proxy("HTTP", "http"),
+        https: proxy("HTTPS", "http"),
+        all: proxy("SOCKS", "socks5"),
+        bypass,
+        ..SystemProxy::default()
+    };
+    if proxy.is_empty() && get("ProxyAutoConfigEnable").as_deref() == Some("1") {
+        proxy.pac_location =
+            get("ProxyAutoConfigURLString").and_then(|location| pac_location(&location));
+    }
+
+    (!proxy.is_empty()).then_some(proxy)
+}
+
+#[cfg(all(test, feature = "pac"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_pac() {
+        let system = SystemProxy {
+            https: proxy_url("secure.corp:8443"),
+            bypass: vec!["intranet.corp".into()],
+            pac: Some(Arc::new(Pac::new(
+                r#"function FindProxyForURL(url, host) { return "PROXY proxy.corp:8080"; }"#
+                    .to_string(),
+            ))),
+            ..SystemProxy::default()
+        };
+        let url = Url::parse("http://nodejs.org/dist/index.json").unwrap();
+
+        // the PAC file is not run while resolving, the host is looked up beforehand
+        assert_eq!(system.resolve(&url), None);
+        system.prepare(&url).await;
+        assert_eq!(
+            system.resolve(&url).unwrap().as_str(),
+            "http://proxy.corp:8080/"
+        );
+
+        // the proxy set for the scheme & the bypass list come first
+        let secure = Url::parse("https://nodejs.org/dist/index.json").unwrap();
+        system.prepare(&secure).await;
+        assert_eq!(
+            system.resolve(&secure).unwrap().as_str(),
+            "http://secure.corp:8443/"
+        );
+        let intranet = Url::parse("http://intranet.corp/dist/").unwrap();
+        system.prepare(&intranet).await;
+        assert_eq!(system.resolve(&intranet), None);
+        assert_eq!(system.pac.as_ref().unwrap().cached(&intranet), None);
+    }
+}
@@ -0,0 +1,327 @@
+use std::{convert::Infallible, fmt, str::FromStr};
+
+use node_semver::Version;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{node::Node, platform::Platform};
+
+/// A node release in the version index (`index.json`)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Release {
+    /// node version, `v` prefixed in the index
+    #[serde(with = "prefixed_version")]
+    pub version: Version,
+
+    /// release date, e.g. `2024-05-28`
+    pub date: String,
+
+    /// the downloadable files
+    pub files: Vec<ReleaseFile>,
+
+    /// npm version
+    pub npm: Option<String>,
+
+    /// v8 engine version
+    pub v8: Option<String>,
+
+    /// libuv version
+    pub uv: Option<String>,
+
+    /// zlib version
+    pub zlib: Option<String>,
+
+    /// openssl version
+    pub openssl: Option<String>,
+
+    /// `NODE_MODULE_VERSION` of the native addon ABI
+    pub modules: Option<String>,
+
+    /// long time support
+    #[serde(default)]
+    pub lts: Lts,
+
+    /// whether the release fixes security issues
+    #[serde(default)]
+    pub security: bool,
+}
+
+/// Long time support of a release
+/// `false` or the codename (e.g. `Iron`) in the index
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Lts {
+    /// not a long time support release
+    #[default]
+    None,
+
+    /// a long time support release of the codename line
+    Codename(String),
+}
+
+impl Lts {
+    pub fn is_lts(&self) -> bool {
+        matches!(self, Lts::Codename(_))
+    }
+
+    pub fn codename(&self) -> Option<&str> {
+        match self {
+            Lts::Codename(codename) => Some(codename),
+            Lts::None => None,
+        }
+    }
+}
+
+impl Serialize for Lts {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Lts::Codename(codename) => serializer.serialize_str(codename),
+            Lts::None => serializer.serialize_bool(false),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Lts {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match serde_json::Value::deserialize(deserializer)? {
+            serde_json::Value::String(codename) if !codename.is_empty() => Lts::Codename(codename),
+            _ => Lts::None,
+        })
+    }
+}
+
+/// An entry of the `files` array of a release
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReleaseFile {
+    /// `headers`
+    Headers,
+
+    /// `src`
+    Source,
+
+    /// a prebuilt binary, e.g. `linux-x64`, `osx-arm64-tar`, `win-x64-zip`
+    Binary {
+        /// `linux`, `osx`, `win`, `aix`, `sunos` ...
+        os: String,
+
+        /// `x64`, `arm64`, `armv7l`, `x86` ...
+        arch: String,
+
+        /// `tar`, `zip`, `pkg`, `msi`, `7z`, `exe`, `musl` ...
+        /// `None` for the plain tarballs of Linux & other Unixes
+        format: Option<String>,
+    },
+
+    /// anything else, kept as is
+    Other(String),
+}
+
+impl FromStr for ReleaseFile {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "headers" => ReleaseFile::Headers,
+            "src" => ReleaseFile::Source,
+            _ => {
+                let mut parts = s.splitn(3, '-');
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some(os), Some(arch), format) if !os.is_empty() && !arch.is_empty() => {
+                        ReleaseFile::Binary {
+                            os: os.to_string(),
+                            arch: arch.to_string(),
+                            format: format.map(|format| format.to_string()),
+                        }
+                    }
+                    _ => ReleaseFile::Other(s.to_string()),
+                }
+            }
+        })
+    }
+}
+
+impl fmt::Display for ReleaseFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReleaseFile::Headers => write!(f, "headers"),
+            ReleaseFile::Source => write!(f, "src"),
+            ReleaseFile::Binary {
+                os,
+                arch,
+                format: Some(format),
+            } => write!(f, "{os}-{arch}-{format}"),
+            ReleaseFile::Binary { os, arch, .. } => write!(f, "{os}-{arch}"),
+            ReleaseFile::Other(file) => write!(f, "{file}"),
+        }
+    }
+}
+
+impl Serialize for ReleaseFile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ReleaseFile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let file = String::deserialize(deserializer)?;
+        Ok(file.parse().unwrap_or(ReleaseFile::Other(file)))
+    }
+}
+
+/// (De)serialize a version with the `v` prefix used by the index
+mod prefixed_version {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(version: &Version, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("v{version}"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Version, D::Error> {
+        let version = String::deserialize(deserializer)?;
+        let version = version.trim();
+        Version::parse(version.strip_prefix('v').unwrap_or(version)).map_err(de::Error::custom)
+    }
+}
+
+impl Release {
+    /// Whether the release ships a prebuilt binary for `platform`
+    pub fn is_available(&self, platform: &Platform) -> bool {
+        let identifier = Node::file_identifier(&self.version, platform);
+        self.files.iter().any(|file| file.to_string() == identifier)
+    }
+}
+
+/// The version index, the latest release first
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "Vec<Release>", into = "Vec<Release>")]
+pub struct Releases(Vec<Release>);
+
+impl From<Vec<Release>> for Releases {
+    fn from(mut releases: Vec<Release>) -> Self {
+        releases.sort_by(|a, b| b.version.cmp(&a.version));
+        Releases(releases)
+    }
+}
+
+impl From<Releases> for Vec<Release> {
+    fn from(releases: Releases) -> Self {
+        releases.0
+    }
+}
+
+impl Releases {
+    /// all the releases, the latest first
+    pub fn all(&self) -> &[Release] {
+        &self.0
+    }
+
+    pub fn into_vec(self) -> Vec<Release> {
+        self.0
+    }
+
+    /// the release of `version`
+    pub fn get(&self, version: &Version) -> Option<&Release> {
+        self.0.iter().find(|release| &release.version == version)
+    }
+
+    /// the latest release
+    pub fn latest(&self) -> Option<&Release> {
+        self.0.first()
+    }
+
+    /// the latest long time support release
+    pub fn latest_lts(&self) -> Option<&Release> {
+        self.0.iter().find(|release| release.lts.is_lts())
+    }
+
+    /// the releases of the LTS line `codename` (case insensitive), the latest first
+    pub fn by_codename<'a>(&'a self, codename: &'a str) -> impl Iterator<Item = &'a Release> {
+        self.0.iter().filter(move |release| {
+            release
+                .lts
+                .codename()
+                .is_some_and(|name| name.eq_ignore_ascii_case(codename))
+        })
+    }
+
+    /// the releases of the `major` version line, the latest first
+    pub fn by_major(&self, major: u64) -> impl Iterator<Item = &Release> {
+        self.0
+            .iter()
+            .filter(move |release| release.version.major == major)
+    }
+
+    /// the releases with a prebuilt binary for `platform`, the latest first
+    pub fn available<'a>(&'a self, platform: &'a Platform) -> impl Iterator<Item = &'a Release> {
+        self.0
+            .iter()
+            .filter(move |release| release.is_available(platform))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::Os;
+
+    const INDEX: &str = r#"[
+        {"version":"v20.14.0","date":"2024-05-28","files":["headers","linux-x64","osx-arm64-tar","win-x64-zip","src"],"npm":"10.7.0","v8":"11.3.244.8","uv":"1.46.0","zlib":"1.3.0.1-motley","openssl":"3.0.13+quic","modules":"115","lts":"Iron","security":false},
+        {"version":"v22.2.0","date":"2024-05-15","files":["linux-x64","osx-arm64-tar"],"npm":"10.7.0","v8":"12.4.254.15","uv":"1.48.0","zlib":"1.3.0.1-motley","openssl":"3.0.13+quic","modules":"127","lts":false,"security":false},
+        {"version":"v18.20.3","date":"2024-05-21","files":["linux-x64"],"lts":"Hydrogen","security":true},
+        {"version":"v0.1.14","date":"2011-08-26","files":["src"],"lts":false}
+    ]"#;
+
+    #[test]
+    fn test_release_file() {
+        let file: ReleaseFile = "osx-arm64-tar".parse().unwrap();
+        assert_eq!(
+            file,
+            ReleaseFile::Binary {
+                os: "osx".into(),
+                arch: "arm64".into(),
+                format: Some("tar".into())
+            }
+        );
+        for file in [
+            "headers",
+            "src",
+            "linux-x64",
+            "win-x86-7z",
+            "linux-x64-musl",
+        ] {
+            assert_eq!(file.parse::<ReleaseFile>().unwrap().to_string(), file);
+        }
+    }
+
+    #[test]
+    fn test_releases() {
+        let releases: Releases = serde_json::from_str(INDEX).unwrap();
+        assert_eq!(releases.latest().unwrap().version.to_string(), "22.2.0");
+        assert_eq!(
+            releases.latest_lts().unwrap().version.to_string(),
+            "20.14.0"
+        );
+        assert_eq!(releases.by_codename("hydrogen").count(), 1);
+        assert_eq!(
+            releases.by_major(18).next().unwrap().lts.codename(),
+            Some("Hydrogen")
+        );
+        assert!(!releases.all()[1].security);
+        assert_eq!(releases.all()[1].modules.as_deref(), Some("115"));
+        assert!(releases.all()[2].security);
+        assert_eq!(releases.all()[3].npm, None);
+
+        let windows = Platform::new(Os::Windows, "x64");
+        assert_eq!(releases.available(&windows).count(), 1);
+        assert!(releases.all()[0].is_available(&Platform::new(Os::Darwin, "arm64")));
+    }
+
+    #[test]
+    fn test_release_serialize() {
+        let releases: Vec<Release> = serde_json::from_str(INDEX).unwrap();
+        let value = serde_json::to_value(&releases[0]).unwrap();
+        assert_eq!(value["version"], "v20.14.0");
+        assert_eq!(value["lts"], "Iron");
+        assert_eq!(value["files"][2], "osx-arm64-tar");
+        assert_eq!(serde_json::to_value(&releases[1]).unwrap()["lts"], false);
+    }
+}
@@ -0,0 +1,190 @@
+use std::str::FromStr;
+
+use anyhow::{Error, Result};
+use node_semver::{Range, Version};
+use serde::{Deserialize, Serialize};
+
+use super::release::Releases;
+
+/// A version specifier, as accepted by nvm
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionSpec {
+    /// `latest` or `node`
+    Latest,
+
+    /// `lts/*` or `lts`, the latest long time support release
+    Lts,
+
+    /// `lts/<codename>`, e.g. `lts/iron`
+    Codename(String),
+
+    /// a full version, e.g. `20.11.1` or `v20.11.1`
+    Exact(Version),
+
+    /// a partial version (`20`, `v20.11`) or a semver range (`^18`, `>=16 <20`)
+    Range(Range),
+}
+
+/// Where to look for a matching version first
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Prefer {
+    /// an installed version, the release index when none is installed
+    #[default]
+    Installed,
+
+    /// a released version, the installed ones when the index has none
+    Remote,
+}
+
+impl FromStr for VersionSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let spec = s.trim();
+        let lower = spec.to_ascii_lowercase();
+
+        Ok(match lower.as_str() {
+            "" => anyhow::bail!("version should not be null"),
+            "latest" | "node" | "current" => VersionSpec::Latest,
+            "lts" | "lts/*" => VersionSpec::Lts,
+            _ => match lower.strip_prefix("lts/") {
+                Some(codename) => VersionSpec::Codename(codename.to_string()),
+                None => {
+                    // `v20` & `v20.11.1` are accepted like nvm does
+                    let version = spec
+                        .strip_prefix(['v', 'V'])
+                        .filter(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+                        .unwrap_or(spec);
+                    match Version::parse(version) {
+                        Ok(version) => VersionSpec::Exact(version),
+                        Err(_) => VersionSpec::Range(Range::parse(version)?),
+                    }
+                }
+            },
+        })
+    }
+}
+
+impl VersionSpec {
+    /// Whether `version` matches, the release index tells the LTS lines apart
+    pub fn matches(&self, version: &Version, releases: &Releases) -> bool {
+        match self {
+            VersionSpec::Latest => true,
+            VersionSpec::Lts => releases
+                .get(version)
+                .is_some_and(|release| release.lts.is_lts()),
+            VersionSpec::Codename(codename) => releases.get(version).is_some_and(|release| {
+                release
+                    .lts
+                    .codename()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(codename))
+            }),
+            VersionSpec::Exact(exact) => exact == version,
+            VersionSpec::Range(range) => range.satisfies(version),
+        }
+    }
+
+    /// The highest version of `versions` that matches
+    fn best<'a>(
+        &self,
+        versions: impl Iterator<Item = &'a Version>,
+        releases: &Releases,
+    ) -> Option<Version> {
+        versions
+            .filter(|version| self.matches(version, releases))
+            .max()
+            .cloned()
+    }
+}
+
+/// Resolve the specifier `spec` to a concrete version
+///
+/// An exact version is returned as is, otherwise the highest matching version
+/// is picked from the `installed` versions or the `releases` index, according to `prefer`.
+pub fn resolve(
+    spec: &str,
+    releases: &Releases,
+    installed: &[Version],
+    prefer: Prefer,
+) -> Result<Version> {
+    let version_spec: VersionSpec = spec.parse()?;
+    if let VersionSpec::Exact(version) = version_spec {
+        return Ok(version);
+    }
+
+    let from_installed = || version_spec.best(installed.iter(), releases);
+    let from_remote = || {
+        let released = releases.all().iter().map(|release| &release.version);
+        version_spec.best(released, releases)
+    };
+
+    let version = match prefer {
+        Prefer::Installed => from_installed().or_else(from_remote),
+        Prefer::Remote => from_remote().or_else(from_installed),
+    };
+
+    version.ok_or_else(|| {
+        super::Error::VersionNotFound {
+            spec: spec.to_string(),
+        }
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INDEX: &str = r#"[
+        {"version":"v22.2.0","date":"2024-05-15","files":[],"lts":false},
+        {"version":"v20.14.0","date":"2024-05-28","files":[],"lts":"Iron"},
+        {"version":"v20.11.1","date":"2024-02-14","files":[],"lts":"Iron"},
+        {"version":"v18.20.3","date":"2024-05-21","files":[],"lts":"Hydrogen"},
+        {"version":"v18.20.2","date":"2024-04-10","files":[],"lts":"Hydrogen"}
+    ]"#;
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn test_version_spec() {
+        assert_eq!("node".parse::<VersionSpec>().unwrap(), VersionSpec::Latest);
+        assert_eq!("lts/*".parse::<VersionSpec>().unwrap(), VersionSpec::Lts);
+        assert_eq!(
+            "lts/Iron".parse::<VersionSpec>().unwrap(),
+            VersionSpec::Codename("iron".into())
+        );
+        assert_eq!(
+            "v20.11.1".parse::<VersionSpec>().unwrap(),
+            VersionSpec::Exact(version("20.11.1"))
+        );
+        assert!(matches!(
+            "v20".parse::<VersionSpec>().unwrap(),
+            VersionSpec::Range(_)
+        ));
+        assert!("".parse::<VersionSpec>().is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let releases: Releases = serde_json::from_str(INDEX).unwrap();
+        let installed = [version("20.11.1"), version("18.20.2")];
+        let resolve = |spec, prefer| resolve(spec, &releases, &installed, prefer).unwrap();
+
+        assert_eq!(resolve("latest", Prefer::Remote), version("22.2.0"));
+        assert_eq!(resolve("node", Prefer::Installed), version("20.11.1"));
+        assert_eq!(resolve("lts/*", Prefer::Remote), version("20.14.0"));
+        assert_eq!(
+            resolve("lts/hydrogen", Prefer::Installed),
+            version("18.20.2")
+        );
+        assert_eq!(resolve("lts/hydrogen", Prefer::Remote), version("18.20.3"));
+        assert_eq!(resolve("^18", Prefer::Remote), version("18.20.3"));
+        assert_eq!(resolve("20", Prefer::Installed), version("20.11.1"));
+        assert_eq!(resolve("20.14", Prefer::Installed), version("20.14.0"));
+        assert_eq!(resolve("16.0.0", Prefer::Remote), version("16.0.0"));
+        assert!(super::resolve("lts/gallium", &releases, &installed, Prefer::Remote).is_err());
+    }
+}
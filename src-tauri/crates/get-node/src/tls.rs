@@ -0,0 +1,112 @@
+use std::sync::OnceLock;
+
+use anyhow::{bail, Context, Result};
+use reqwest::{Certificate, ClientBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::Error;
+
+/// The TLS settings of the http clients, used for the index & the archives
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Tls {
+    /// PEM files of extra CA certificates to trust (bundles too), e.g. a corporate CA
+    #[serde(default)]
+    pub ca_files: Vec<String>,
+
+    /// trust the CA certificates of the OS store as well as the built-in ones
+    #[serde(default)]
+    pub native_roots: bool,
+}
+
+impl Tls {
+    /// Add the CA certificates to the roots of `builder`
+    pub(crate) async fn apply(&self, mut builder: ClientBuilder) -> Result<ClientBuilder> {
+        for path in self.ca_files.iter().map(|path| path.trim()) {
+            if path.is_empty() {
+                continue;
+            }
+            let pem = tokio::fs::read(path)
+                .await
+                .with_context(|| format!("Failed to read the CA certificates \"{}\"", path))?;
+            let certificates = Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid PEM CA certificates \"{}\"", path))?;
+            if certificates.is_empty() {
+                bail!("No CA certificate found in \"{}\"", path);
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        if self.native_roots {
+            for certificate in native_roots() {
+                builder = builder.add_root_certificate(certificate.clone());
+            }
+        }
+        Ok(builder)
+    }
+}
+
+/// The CA certificates of the OS store, loaded once
+/// the ones that can not be read are skipped
+fn native_roots() -> &'static [Certificate] {
+    static ROOTS: OnceLock<Vec<Certificate>> = OnceLock::new();
+    ROOTS.get_or_init(|| {
+        rustls_native_certs::load_native_certs()
+            .certs
+            .iter()
+            .filter_map(|der| Certificate::from_der(der.as_ref()).ok())
+            .collect()
+    })
+}
+
+/// Turn a request error into `Error::Tls` when the TLS handshake failed,
+/// e.g. an unknown issuer or an expired certificate
+pub(crate) fn classify(err: reqwest::Error) -> anyhow::Error {
+    // the innermost cause is the rustls one, e.g. `invalid peer certificate: UnknownIssuer`
+    let mut reason = None;
+    let mut source = std::error::Error::source(&err);
+    while let Some(cause) = source {
+        let message = cause.to_string();
+        let lower = message.to_ascii_lowercase();
+        if ["certificate", "tls", "handshake"]
+            .iter()
+            .any(|word| lower.contains(word))
+        {
+            reason = Some(message);
+        }
+        source = cause.source();
+    }
+
+    match reason {
+        Some(reason) => {
+            let host = err
+                .url()
+                .and_then(|url| url.host_str())
+                .unwrap_or_default()
+                .to_string();
+            anyhow::Error::new(Error::Tls { host, reason })
+        }
+        None => err.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_invalid_ca_file() {
+        let path = std::env::temp_dir().join("get-node-test-invalid-ca.pem");
+        tokio::fs::write(&path, "not a certificate").await.unwrap();
+
+        let tls = Tls {
+            ca_files: vec![path.to_string_lossy().to_string()],
+            native_roots: false,
+        };
+        let err = tls.apply(ClientBuilder::new()).await.unwrap_err();
+        assert!(err.to_string().contains("CA certificate"));
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+}
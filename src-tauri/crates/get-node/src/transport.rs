@@ -0,0 +1,263 @@
+use std::{
+    future::Future,
+    io::{ErrorKind, SeekFrom},
+    pin::Pin,
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::{
+    header::{CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_NONE_MATCH, IF_RANGE, RANGE},
+    Body, Method, Request, Response, StatusCode, Url,
+};
+use tokio::{fs::File, io::AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+use super::{
+    archive::create_client,
+    mirror::{Endpoint, Mirror},
+    net::Timeouts,
+    tls::{self, Tls},
+    Proxy,
+};
+
+/// The response of a request sent by a transport
+pub type Sending<'a> = Pin<Box<dyn Future<Output = Result<Response>> + Send + 'a>>;
+
+/// How the requests to the mirrors are sent
+///
+/// `reqwest::Client` sends them over http(s), `FileTransport` reads a local directory,
+/// any other one (a preconfigured client, a mock ...) can be set in the fetch & list configs.
+pub trait Transport: Send + Sync {
+    /// Send `request`, a response is returned whatever its status
+    fn send(&self, request: Request) -> Sending<'_>;
+}
+
+impl Transport for reqwest::Client {
+    fn send(&self, request: Request) -> Sending<'_> {
+        Box::pin(async move { self.execute(request).await.map_err(tls::classify) })
+    }
+}
+
+/// Serve a mirror from a local directory, e.g. a USB stick or an NFS share
+///
+/// A missing file is a `404`, a matching `If-None-Match` a `304`
+/// and `Range: bytes=<start>-` a `206`, so that downloads resume as over http.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FileTransport;
+
+impl Transport for FileTransport {
+    fn send(&self, request: Request) -> Sending<'_> {
+        Box::pin(serve(request))
+    }
+}
+
+async fn serve(request: Request) -> Result<Response> {
+    let head = request.method() == Method::HEAD;
+    if request.method() != Method::GET && !head {
+        return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+    }
+
+    let path = request
+        .url()
+        .to_file_path()
+        .map_err(|_| anyhow!("Invalid local mirror url \"{}\"", request.url()))?;
+    let mut file = match File::open(&path).await {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(status(StatusCode::NOT_FOUND)),
+        Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+            return Ok(status(StatusCode::FORBIDDEN))
+        }
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {:?}", path)),
+    };
+    let metadata = file.metadata().await?;
+    if metadata.is_dir() {
+        return Ok(status(StatusCode::NOT_FOUND));
+    }
+
+    // the size & modification time stand for the content, as most http servers do
+    let size = metadata.len();
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    let etag = format!("\"{:x}-{:x}\"", size, modified.as_secs());
+
+    let header = |name| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    if header(IF_NONE_MATCH).is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag)) {
+        let mut response = status(StatusCode::NOT_MODIFIED);
+        response.headers_mut().insert(ETAG, etag.parse()?);
+        return Ok(response);
+    }
+
+    // the range is ignored when the file changed since the partial download
+    let start = header(RANGE)
+        .and_then(range_start)
+        .filter(|_| header(IF_RANGE).unwrap_or(&etag) == etag);
+    let mut builder = http::Response::builder().header(ETAG, &etag);
+    let start = match start {
+        Some(start) if start < size => {
+            builder = builder.status(StatusCode::PARTIAL_CONTENT).header(
+                CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, size - 1, size),
+            );
+            start
+        }
+        Some(_) => {
+            let mut response = status(StatusCode::RANGE_NOT_SATISFIABLE);
+            response
+                .headers_mut()
+                .insert(CONTENT_RANGE, format!("bytes */{}", size).parse()?);
+            return Ok(response);
+        }
+        None => 0,
+    };
+    builder = builder.header(CONTENT_LENGTH, size - start);
+
+    let body = if head {
+        Body::from(Vec::new())
+    } else {
+        file.seek(SeekFrom::Start(start)).await?;
+        Body::wrap_stream(ReaderStream::new(file))
+    };
+    Ok(builder.body(body)?.into())
+}
+
+/// An empty response with `status`
+fn status(status: StatusCode) -> Response {
+    let mut response = http::Response::new(Body::from(Vec::new()));
+    *response.status_mut() = status;
+    response.into()
+}
+
+/// The start of a `bytes=<start>-` range, the other ranges are not supported
+fn range_start(range: &str) -> Option<u64> {
+    range
+        .trim()
+        .strip_prefix("bytes=")?
+        .strip_suffix('-')?
+        .parse()
+        .ok()
+}
+
+/// The url of `target` on a mirror, a `file://` url when it is a local path
+pub fn url(target: &str) -> Result<Url> {
+    match Url::parse(target) {
+        // `C:\node` parses as a url with the scheme `c`
+        Ok(url) if url.scheme().len() > 1 => Ok(url),
+        _ => Url::from_file_path(target)
+            .map_err(|_| anyhow!("The local mirror \"{}\" should be an absolute path", target)),
+    }
+}
+
+/// Whether `base` is a local mirror, a `file://` url or a directory path
+pub fn is_local(base: &str) -> bool {
+    url(base).map(|url| url.scheme() == "file").unwrap_or(true)
+}
+
+/// A `GET` request of `target`
+pub fn get(target: &str) -> Result<Request> {
+    Ok(Request::new(Method::GET, url(target)?))
+}
+
+/// The size of the response body, the `Content-Length` header when it is streamed
+pub(crate) fn content_length(response: &Response) -> Option<u64> {
+    response.content_length().or_else(|| {
+        response
+            .headers()
+            .get(CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    })
+}
+
+/// The transport of the requests to `mirror` for `endpoint`:
+/// `custom` when set, `FileTransport` for a local mirror, an http client otherwise
+pub(crate) async fn for_mirror(
+    custom: Option<&Arc<dyn Transport>>,
+    proxy: Option<Proxy>,
+    no_proxy: Option<bool>,
+    tls: Option<&Tls>,
+    mirror: &Mirror,
+    endpoint: Endpoint,
+    timeouts: Timeouts,
+) -> Result<Arc<dyn Transport>> {
+    if let Some(custom) = custom {
+        return Ok(custom.clone());
+    }
+    if is_local(mirror.base(endpoint)) {
+        return Ok(Arc::new(FileTransport));
+    }
+
+    Ok(Arc::new(
+        create_client(proxy, no_proxy, tls, mirror, timeouts).await?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url() {
+        assert!(!is_local("https://nodejs.org/dist"));
+        assert!(is_local("file:///mnt/usb/node"));
+        assert!(is_local("/mnt/usb/node"));
+        assert!(is_local("C:\\node"));
+        assert!(url("node/dist").is_err());
+
+        #[cfg(unix)]
+        assert_eq!(
+            url("/mnt/usb/node mirror/index.json").unwrap().as_str(),
+            "file:///mnt/usb/node%20mirror/index.json"
+        );
+        assert_eq!(range_start("bytes=1024-"), Some(1024));
+        assert_eq!(range_start("bytes=0-99"), None);
+    }
+
+    #[tokio::test]
+    async fn test_file_transport() {
+        let root = std::env::temp_dir().join("get-node-test-file-transport");
+        let _ = tokio::fs::remove_dir_all(&root).await;
+        tokio::fs::create_dir_all(&root).await.unwrap();
+        let path = root.join("SHASUMS256.txt");
+        tokio::fs::write(&path, "0123456789").await.unwrap();
+        let target = path.to_string_lossy().to_string();
+
+        let response = FileTransport.send(get(&target).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(content_length(&response), Some(10));
+        let etag = response.headers()[ETAG].clone();
+        assert_eq!(response.text().await.unwrap(), "0123456789");
+
+        let mut request = get(&target).unwrap();
+        request.headers_mut().insert(IF_NONE_MATCH, etag.clone());
+        let response = FileTransport.send(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let mut request = get(&target).unwrap();
+        request
+            .headers_mut()
+            .insert(RANGE, "bytes=4-".parse().unwrap());
+        request.headers_mut().insert(IF_RANGE, etag);
+        let response = FileTransport.send(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[CONTENT_RANGE], "bytes 4-9/10");
+        assert_eq!(response.text().await.unwrap(), "456789");
+
+        let missing = root.join("index.json").to_string_lossy().to_string();
+        let response = FileTransport.send(get(&missing).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let _ = tokio::fs::remove_dir_all(&root).await;
+    }
+}
@@ -1,10 +1,17 @@
 use anyhow::Result;
+use get_node::{
+    archive::FetchResult, cache::CacheEntry, janitor::Leftover, mirror::Benchmark, resolver::Prefer,
+};
 use std::{path::PathBuf, process::Command};
 use tauri_plugin_window_state::{AppHandleExt, StateFlags};
 
 use crate::{
-    config::{Config, Group, ISettings, NVersion, Project},
-    core::{configuration, group, handle, node, project},
+    config::{Config, Group, ISettings, Project},
+    core::{
+        configuration, group, handle,
+        jobs::{self, Job},
+        node, project,
+    },
     ret_err,
     utils::dirs,
     wrap_err,
@@ -20,13 +27,19 @@ pub fn current(fetch: Option<bool>) -> CmdResult<Option<String>> {
 
 /// set current version
 #[tauri::command]
-pub async fn set_current(version: Option<String>) -> CmdResult<()> {
-    wrap_err!(node::set_current(version).await)
+pub async fn set_current(version: Option<String>, prefer: Option<Prefer>) -> CmdResult<()> {
+    wrap_err!(node::set_current(version, prefer).await)
+}
+
+/// resolve a version specifier to a concrete version
+#[tauri::command]
+pub async fn resolve_version(version: String, prefer: Option<Prefer>) -> CmdResult<String> {
+    wrap_err!(node::resolve_version(&version, prefer.unwrap_or_default()).await)
 }
 
 /// fetch node version list
 #[tauri::command]
-pub async fn version_list(fetch: Option<bool>) -> CmdResult<Option<Vec<NVersion>>> {
+pub async fn version_list(fetch: Option<bool>) -> CmdResult<node::VersionList> {
     wrap_err!(node::get_version_list(fetch).await)
 }
 
@@ -50,6 +63,8 @@ pub async fn update_settings(settings: ISettings) -> CmdResult<()> {
 
     wrap_err!({ Config::settings().draft().patch_settings(settings.clone()) })?;
     Config::settings().apply();
+    // the queued installs may start with a higher concurrency, the downloads speed up or slow down
+    jobs::apply_settings();
 
     // refresh data when directory changes
     if directory != settings.directory {
@@ -69,14 +84,87 @@ pub async fn install_node(
     window: tauri::Window,
     version: Option<String>,
     arch: Option<String>,
-) -> CmdResult<String> {
-    wrap_err!(node::install_node(window, version, arch).await)
+    prefer: Option<Prefer>,
+    download_limit: Option<u64>,
+) -> CmdResult<FetchResult> {
+    wrap_err!(node::install_node(window, version, arch, prefer, download_limit).await)
 }
 
-/// install node
+/// cancel the install job `id`, every install when `None`
+#[tauri::command]
+pub async fn install_node_cancel(id: Option<u64>) -> CmdResult<()> {
+    wrap_err!(node::install_node_cancel(id).await)
+}
+
+/// the installs running & waiting in the queue
+#[tauri::command]
+pub async fn install_jobs() -> CmdResult<Vec<Job>> {
+    wrap_err!(node::install_jobs().await)
+}
+
+/// move a queued install to `position` in the queue
 #[tauri::command]
-pub async fn install_node_cancel() -> CmdResult<()> {
-    wrap_err!(node::install_node_cancel().await)
+pub async fn install_job_move(id: u64, position: usize) -> CmdResult<Vec<Job>> {
+    wrap_err!(node::install_job_move(id, position).await)
+}
+
+/// limit the download bandwidth of an install (KB/s)
+#[tauri::command]
+pub async fn install_job_limit(id: u64, download_limit: Option<u64>) -> CmdResult<Vec<Job>> {
+    wrap_err!(node::install_job_limit(id, download_limit).await)
+}
+
+/// benchmark the mirrors
+#[tauri::command]
+pub async fn mirror_benchmark() -> CmdResult<Vec<Benchmark>> {
+    wrap_err!(node::mirror_benchmark().await)
+}
+
+/// the proxy used for a request to the url
+#[tauri::command]
+pub async fn proxy_for(url: String) -> CmdResult<Option<String>> {
+    wrap_err!(node::proxy_for(url).await)
+}
+
+/// list the leftovers of interrupted installs
+#[tauri::command]
+pub async fn leftover_list() -> CmdResult<Vec<Leftover>> {
+    wrap_err!(node::leftover_list().await)
+}
+
+/// remove leftovers of interrupted installs
+#[tauri::command]
+pub async fn leftover_clean(paths: Vec<String>) -> CmdResult<Vec<Leftover>> {
+    wrap_err!(node::leftover_clean(paths).await)
+}
+
+/// finish the interrupted installs of unrenamed directories
+#[tauri::command]
+pub async fn leftover_finish(paths: Vec<String>) -> CmdResult<Vec<Leftover>> {
+    wrap_err!(node::leftover_finish(paths).await)
+}
+
+/// list the archives in the cache
+#[tauri::command]
+pub async fn cache_list() -> CmdResult<Vec<CacheEntry>> {
+    wrap_err!(node::cache_list().await)
+}
+
+/// download node archive into the cache
+#[tauri::command]
+pub async fn cache_download(
+    window: tauri::Window,
+    version: Option<String>,
+    arch: Option<String>,
+    download_limit: Option<u64>,
+) -> CmdResult<CacheEntry> {
+    wrap_err!(node::cache_download(window, version, arch, download_limit).await)
+}
+
+/// prune the archive cache
+#[tauri::command]
+pub async fn cache_prune(max_size: Option<u64>) -> CmdResult<Vec<CacheEntry>> {
+    wrap_err!(node::cache_prune(max_size).await)
 }
 
 /// uninstall node
@@ -112,14 +200,22 @@ pub async fn update_projects(list: Vec<Project>, path: Option<PathBuf>) -> CmdRe
 
 /// update project version
 #[tauri::command]
-pub async fn sync_project_version(path: PathBuf, version: String) -> CmdResult<i32> {
-    wrap_err!(project::sync_project_version(path, &version).await)
+pub async fn sync_project_version(
+    path: PathBuf,
+    version: String,
+    prefer: Option<Prefer>,
+) -> CmdResult<i32> {
+    wrap_err!(project::sync_project_version(path, &version, prefer).await)
 }
 
 /// batch update project version
 #[tauri::command]
-pub async fn batch_update_project_version(paths: Vec<PathBuf>, version: String) -> CmdResult<()> {
-    wrap_err!(project::batch_update_project_version(paths, version).await)
+pub async fn batch_update_project_version(
+    paths: Vec<PathBuf>,
+    version: String,
+    prefer: Option<Prefer>,
+) -> CmdResult<()> {
+    wrap_err!(project::batch_update_project_version(paths, version, prefer).await)
 }
 
 /// get group list
@@ -136,8 +232,12 @@ pub async fn update_groups(list: Vec<Group>) -> CmdResult<()> {
 
 /// update group version
 #[tauri::command]
-pub async fn update_group_version(name: String, version: String) -> CmdResult<()> {
-    wrap_err!(group::update_group_version(name, version).await)
+pub async fn update_group_version(
+    name: String,
+    version: String,
+    prefer: Option<Prefer>,
+) -> CmdResult<()> {
+    wrap_err!(group::update_group_version(name, version, prefer).await)
 }
 
 /// configration export
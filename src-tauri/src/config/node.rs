@@ -3,34 +3,12 @@ use std::cmp::Ordering;
 use crate::utils::{dirs, help};
 
 use anyhow::Result;
+use get_node::{list::IndexMeta, release::Release};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use version_compare::{compare, Cmp};
 
-#[derive(Default, Debug, Clone, Deserialize, Serialize)]
-pub struct NVersion {
-    /// version release date
-    pub date: String,
-
-    /// long time support version
-    /// String or bool
-    pub lts: Option<Value>,
-
-    /// openssl version
-    pub openssl: Option<String>,
-
-    /// npm version
-    pub npm: Option<String>,
-
-    /// v8 engine version
-    pub v8: String,
-
-    /// node version
-    pub version: String,
-
-    /// the downloadbable files with types
-    pub files: Vec<String>,
-}
+/// a node release of the version index
+pub type NVersion = Release;
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct INode {
@@ -40,6 +18,9 @@ pub struct INode {
     /// node version list
     pub list: Option<Vec<NVersion>>,
 
+    /// where & when the version list was fetched
+    pub meta: Option<IndexMeta>,
+
     /// installed node versions
     pub installed: Option<Vec<String>>,
 }
@@ -62,6 +43,10 @@ impl INode {
                 log::error!(target: "app", "{err}");
                 Some(vec![])
             });
+        // get the list metadata from `versions.meta.json`, missing before the first fetch
+        let meta = dirs::version_meta_path()
+            .and_then(|path| help::read_json::<IndexMeta>(&path))
+            .ok();
 
         let mut installed = directory
             .map(|path| {
@@ -81,6 +66,7 @@ impl INode {
         Self {
             current,
             list,
+            meta,
             installed: Some(installed),
         }
     }
@@ -119,6 +105,11 @@ impl INode {
         self.list.clone()
     }
 
+    /// get version list metadata
+    pub fn get_meta(&self) -> Option<IndexMeta> {
+        self.meta.clone()
+    }
+
     /// get installed
     pub fn get_installed(&self) -> Option<Vec<String>> {
         self.installed.clone()
@@ -136,6 +127,13 @@ impl INode {
         self.save_file()
     }
 
+    /// update version list metadata
+    /// save to `versions.meta.json`
+    pub fn update_meta(&mut self, meta: &IndexMeta) -> Result<()> {
+        self.meta = Some(meta.clone());
+        help::save_json(&dirs::version_meta_path()?, meta, None)
+    }
+
     /// update installed
     pub fn update_installed(&mut self, installed: &Vec<String>) -> Result<()> {
         self.installed = Some(installed.clone());
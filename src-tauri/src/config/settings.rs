@@ -1,8 +1,15 @@
-use crate::utils::{dirs, help};
+use crate::utils::{credentials, dirs, help};
 
 use anyhow::Result;
-use get_node::Proxy;
+use get_node::{
+    archive::SignatureMode,
+    mirror::{Mirror, UNOFFICIAL_MIRROR},
+    net::{Retry, Timeouts},
+    tls::Tls,
+    Proxy,
+};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct ISettings {
@@ -18,9 +25,35 @@ pub struct ISettings {
     /// installation directory
     pub directory: Option<String>,
 
+    /// keep the downloaded archives in the cache
+    /// and install from it when possible
+    pub enable_cache: Option<bool>,
+
+    /// the maximum size of the archive cache (MB)
+    pub cache_limit: Option<u64>,
+
+    /// the version list is refreshed on launch once older than this (hours)
+    /// `0` to refresh it on every launch
+    pub index_ttl: Option<u64>,
+
     /// not show the window on launch
     pub enable_silent_start: Option<bool>,
 
+    /// install the unofficial builds (musl, riscv64, loong64, old glibc) when needed
+    /// and when the official mirrors have no archive for this platform
+    pub enable_unofficial: Option<bool>,
+
+    /// installs running at the same time, the others wait in the queue
+    pub install_concurrency: Option<usize>,
+
+    /// the download bandwidth shared by all the installs (KB/s)
+    /// `0` for no limit, an install may be limited further on its own
+    pub download_limit: Option<u64>,
+
+    /// unpack the tarball while downloading it (Unix only)
+    /// faster and no temp file, but an interrupted download can not be resumed
+    pub enable_streaming: Option<bool>,
+
     /// language
     /// `en` or `zh-CN`
     pub locale: Option<String>,
@@ -28,7 +61,16 @@ pub struct ISettings {
     /// download url
     pub mirror: Option<String>,
 
-    /// proxy ip & ip
+    /// mirrors tried in order when one of them is down
+    /// each one may have separate index & download urls, `mirror` is used when empty
+    /// their credentials are kept in the system keychain, see `get_mirrors`
+    pub mirrors: Option<Vec<Mirror>>,
+
+    /// unofficial builds url
+    pub unofficial_mirror: Option<String>,
+
+    /// proxy scheme, address, user & hosts to bypass
+    /// the password is kept in the system keychain, see `get_proxy`
     pub proxy: Option<Proxy>,
 
     /// disable proxy
@@ -36,6 +78,28 @@ pub struct ISettings {
     /// invalid with `TUN` proxy mode
     pub no_proxy: Option<bool>,
 
+    /// seconds to connect to a mirror or the proxy
+    pub connect_timeout: Option<u64>,
+
+    /// seconds without receiving anything before a request fails, e.g. a stalled download
+    pub read_timeout: Option<u64>,
+
+    /// seconds for a whole request, download included
+    /// `0` for no limit, large downloads on slow links are only stopped when they stall
+    pub total_timeout: Option<u64>,
+
+    /// retries of a request failing with `5xx`, a reset connection or a timeout
+    /// `0` to never retry
+    pub retries: Option<u32>,
+
+    /// extra PEM CA certificates & whether to trust the OS store, for internal mirrors
+    /// a mirror may also skip the certificate verification with its `insecure` flag
+    pub tls: Option<Tls>,
+
+    /// verify the signature of `SHASUMS256.txt` when installing
+    /// value: `require` or `warn` or `skip`
+    pub signature: Option<SignatureMode>,
+
     /// app theme
     /// `system` or `light` or `dark`
     pub theme: Option<String>,
@@ -68,11 +132,24 @@ impl ISettings {
             closer: Some("minimize".into()),
             coder: default_coder(),
             directory: Some(dirs::default_install_dir().to_string_lossy().to_string()),
+            enable_cache: Some(true),
+            cache_limit: Some(1024),
+            index_ttl: Some(24),
             enable_silent_start: Some(false),
+            enable_streaming: Some(false),
+            install_concurrency: Some(2),
+            download_limit: Some(0),
+            enable_unofficial: Some(false),
             locale: Some("en".into()),
             proxy: None,
             mirror: Some("https://nodejs.org/dist".into()),
+            unofficial_mirror: Some(UNOFFICIAL_MIRROR.into()),
             no_proxy: Some(false),
+            connect_timeout: Some(10),
+            read_timeout: Some(30),
+            total_timeout: Some(0),
+            retries: Some(3),
+            signature: Some(SignatureMode::Warn),
             theme: Some("system".into()),
             ..Self::default()
         }
@@ -97,6 +174,107 @@ impl ISettings {
         self.directory.clone()
     }
 
+    /// get the mirrors to try in order, with their credentials from the system keychain
+    pub fn get_mirrors(&self) -> Vec<Mirror> {
+        let mut mirrors: Vec<Mirror> = match &self.mirrors {
+            Some(mirrors) if !mirrors.is_empty() => mirrors.clone(),
+            _ => self.mirror.iter().map(Mirror::new).collect(),
+        };
+        match credentials::read_mirror_credentials() {
+            Ok(saved) => {
+                for mirror in &mut mirrors {
+                    mirror.credentials = saved.get(&credentials::mirror_key(&mirror.url)).cloned();
+                }
+            }
+            Err(err) => {
+                log::error!(target: "app", "failed to read the mirror credentials: {err}")
+            }
+        }
+        mirrors
+    }
+
+    /// get the unofficial builds mirrors, `None` when they are disabled
+    pub fn get_unofficial_mirrors(&self) -> Option<Vec<Mirror>> {
+        if !self.enable_unofficial.unwrap_or(false) {
+            return None;
+        }
+        let mirror = self
+            .unofficial_mirror
+            .as_deref()
+            .unwrap_or(UNOFFICIAL_MIRROR);
+        Some(vec![Mirror::new(mirror)])
+    }
+
+    /// get the proxy with its password from the system keychain
+    pub fn get_proxy(&self) -> Option<Proxy> {
+        let mut proxy = self.proxy.clone()?;
+        if proxy.username.is_some() {
+            match credentials::get_secret(credentials::PROXY_ACCOUNT) {
+                Ok(password) => proxy.password = password,
+                Err(err) => {
+                    log::error!(target: "app", "failed to read the proxy password: {err}")
+                }
+            }
+        }
+        Some(proxy)
+    }
+
+    /// get the archive cache dir, `None` when the cache is disabled
+    pub fn get_cache_dir(&self) -> Option<String> {
+        if !self.enable_cache.unwrap_or(true) {
+            return None;
+        }
+        dirs::cache_dir()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .ok()
+    }
+
+    /// get the maximum size of the archive cache in bytes
+    pub fn get_cache_limit(&self) -> u64 {
+        self.cache_limit.unwrap_or(1024).saturating_mul(1024 * 1024)
+    }
+
+    /// get the timeouts of the requests, `0` means no limit
+    pub fn get_timeouts(&self) -> Timeouts {
+        let default = Timeouts::default();
+        let seconds = |value: Option<u64>, default: Option<Duration>| match value {
+            Some(0) => None,
+            Some(value) => Some(Duration::from_secs(value)),
+            None => default,
+        };
+        Timeouts {
+            connect: seconds(self.connect_timeout, default.connect),
+            read: seconds(self.read_timeout, default.read),
+            total: seconds(self.total_timeout, default.total),
+        }
+    }
+
+    /// get the retry policy of the requests
+    pub fn get_retry(&self) -> Retry {
+        Retry {
+            retries: self.retries.unwrap_or(3),
+            ..Retry::default()
+        }
+    }
+
+    /// get how many installs may run at the same time
+    pub fn get_install_concurrency(&self) -> usize {
+        self.install_concurrency.unwrap_or(2).max(1)
+    }
+
+    /// get the download bandwidth shared by all the installs in bytes per second
+    /// `None` for no limit
+    pub fn get_download_limit(&self) -> Option<u64> {
+        self.download_limit
+            .filter(|limit| *limit > 0)
+            .map(|limit| limit.saturating_mul(1024))
+    }
+
+    /// get how long the version list is fresh in seconds
+    pub fn get_index_ttl(&self) -> u64 {
+        self.index_ttl.unwrap_or(24).saturating_mul(60 * 60)
+    }
+
     /// update settings config
     /// save to file
     pub fn patch_settings(&mut self, patch: ISettings) -> Result<()> {
@@ -111,11 +289,44 @@ impl ISettings {
         patch!(closer);
         patch!(coder);
         patch!(directory);
+        patch!(enable_cache);
+        patch!(cache_limit);
+        patch!(index_ttl);
         patch!(enable_silent_start);
+        patch!(enable_streaming);
+        patch!(enable_unofficial);
+        patch!(install_concurrency);
+        patch!(download_limit);
         patch!(locale);
         patch!(mirror);
+        // the credentials go to the system keychain, never to `setting.json`
+        if let Some(mirrors) = patch.mirrors.as_deref() {
+            credentials::update_mirror_credentials(mirrors)?;
+        }
+        patch!(mirrors);
+        for mirror in self.mirrors.iter_mut().flatten() {
+            mirror.credentials = None;
+        }
+        patch!(unofficial_mirror);
+        // the password goes to the system keychain, never to `setting.json`
+        if let Some(password) = patch
+            .proxy
+            .as_ref()
+            .and_then(|proxy| proxy.password.as_ref())
+        {
+            credentials::set_secret(credentials::PROXY_ACCOUNT, password)?;
+        }
         patch!(proxy);
+        if let Some(proxy) = self.proxy.as_mut() {
+            proxy.password = None;
+        }
         patch!(no_proxy);
+        patch!(connect_timeout);
+        patch!(read_timeout);
+        patch!(total_timeout);
+        patch!(retries);
+        patch!(tls);
+        patch!(signature);
         patch!(theme);
 
         self.save_file()
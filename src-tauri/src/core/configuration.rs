@@ -2,7 +2,10 @@ use super::{handle, project::sync_project_version};
 use crate::{
     config::{Config, Group, ISettings, Project},
     log_err,
-    utils::help::{async_read_json, async_save_json},
+    utils::{
+        credentials::{self, MirrorCredentials},
+        help::{async_read_json, async_save_json},
+    },
 };
 use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
@@ -23,6 +26,9 @@ pub struct ConfigurationExport {
 
     /// export projects data (include groups)
     projects: Option<bool>,
+
+    /// export the credentials of the mirrors too (secrets, only with `setting`)
+    credentials: Option<bool>,
 }
 
 #[derive(Default, Debug, Deserialize, Serialize)]
@@ -36,6 +42,9 @@ pub struct ConfigurationData {
     /// export mirrors data
     mirrors: Option<String>,
 
+    /// export the credentials of the mirrors
+    credentials: Option<MirrorCredentials>,
+
     /// export projects data
     projects: Option<Vec<Project>>,
 
@@ -65,6 +74,7 @@ pub async fn configuration_export(
         setting,
         mirrors,
         projects,
+        credentials: with_credentials,
     } = configuration;
 
     let mut output = ConfigurationData::default();
@@ -76,6 +86,10 @@ pub async fn configuration_export(
     if setting.unwrap_or(false) {
         output.setting = Some(Config::settings().latest().clone());
         output.mirrors = mirrors;
+        // secrets are only exported when asked for
+        if with_credentials.unwrap_or(false) {
+            output.credentials = Some(credentials::read_mirror_credentials()?);
+        }
     }
     // export projects & groups data
     if projects.unwrap_or(false) {
@@ -103,6 +117,13 @@ pub async fn configuration_import(
             FilePath::Url(_) => bail!("Unsupported URL scheme"),
         };
         let configuration = async_read_json::<ConfigurationData>(&path).await?;
+        // the settings are applied by the page, the credentials are saved right away
+        if let Some(imported) = configuration.credentials.filter(|list| !list.is_empty()) {
+            let mut saved = credentials::read_mirror_credentials()?;
+            saved.extend(imported);
+            credentials::save_mirror_credentials(&saved)?;
+        }
+
         let projects = configuration.projects.unwrap_or_default();
         let groups = configuration.groups.unwrap_or_default();
 
@@ -118,7 +139,7 @@ pub async fn configuration_import(
                 }
 
                 if let Some(version) = version {
-                    sync_project_version(PathBuf::from(&project.path), &version).await?;
+                    sync_project_version(PathBuf::from(&project.path), &version, None).await?;
                 }
             }
         }
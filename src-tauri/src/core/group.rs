@@ -2,8 +2,9 @@ use crate::{
     config::{Config, Group}, log_err, utils::{dirs, help}
 };
 use anyhow::Result;
+use get_node::resolver::Prefer;
 
-use super::handle;
+use super::{handle, node::resolve_optional_version};
 
 /// get project list from `projects.json`
 pub async fn group_list(fetch: Option<bool>) -> Result<Option<Vec<Group>>> {
@@ -34,7 +35,13 @@ pub async fn update_groups(list: Vec<Group>) -> Result<()> {
 }
 
 /// update group version
-pub async fn update_group_version(name: String, version: String) -> Result<()> {
+/// `version` may be a specifier, resolved from the installed versions first by default
+pub async fn update_group_version(
+    name: String,
+    version: String,
+    prefer: Option<Prefer>,
+) -> Result<()> {
+    let version = resolve_optional_version(&version, prefer.unwrap_or_default()).await?;
     Config::groups().draft().update_version(name, version)?;
     Config::groups().apply();
     Config::groups().data().save_file()?;
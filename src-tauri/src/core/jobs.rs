@@ -0,0 +1,353 @@
+use std::{
+    future::Future,
+    sync::{Mutex, MutexGuard},
+};
+
+use anyhow::{bail, Result};
+use get_node::{net::RateLimit, platform::Platform};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tauri::Emitter;
+use tokio::sync::{watch, Notify};
+
+use crate::{config::Config, core::handle};
+
+static JOBS: Lazy<Mutex<Jobs>> = Lazy::new(|| Mutex::new(Jobs::default()));
+
+/// notified whenever a job is added, started, cancelled, moved or done
+static CHANGED: Lazy<Notify> = Lazy::new(Notify::new);
+
+/// the `download_limit` shared by all the jobs, the limit of each job obeys it as well
+static DOWNLOAD_LIMIT: Lazy<RateLimit> =
+    Lazy::new(|| RateLimit::new(Config::settings().latest().get_download_limit()));
+
+/// What a job fetches the archive for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    /// install a version
+    Install,
+
+    /// download an archive into the cache only
+    Download,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// waiting for a running job to be done
+    Queued,
+
+    Running,
+}
+
+/// An install waiting in the queue or running
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: u64,
+
+    pub kind: JobKind,
+
+    /// node version
+    pub version: String,
+
+    /// node architecture, `None` for the one of the system
+    pub arch: Option<String>,
+
+    pub status: JobStatus,
+
+    /// the download bandwidth of this job alone (KB/s), `None` for no limit
+    pub download_limit: Option<u64>,
+}
+
+struct Entry {
+    job: Job,
+
+    cancel: watch::Sender<bool>,
+
+    download_limit: RateLimit,
+}
+
+#[derive(Default)]
+struct Jobs {
+    /// id of the last job queued, `0` before the first one
+    last_id: u64,
+
+    /// in the order the queued jobs are started
+    list: Vec<Entry>,
+}
+
+impl Jobs {
+    /// Queue `entry` with the next id
+    ///
+    /// Rejected while a job of the same version & arch is queued or running,
+    /// they would unpack into the same staging dir & download to the same `.part` file.
+    fn queue(&mut self, mut entry: Entry) -> Result<u64> {
+        let platform = |arch: &Option<String>| Platform::current().with_arch(arch.clone());
+        let target = platform(&entry.job.arch);
+        if let Some(other) = self.list.iter().find(|other| {
+            other.job.version == entry.job.version && platform(&other.job.arch) == target
+        }) {
+            bail!(
+                "Node {} ({}) is already being installed, see job {}",
+                entry.job.version,
+                target.arch,
+                other.job.id
+            );
+        }
+
+        self.last_id += 1;
+        entry.job.id = self.last_id;
+        self.list.push(entry);
+        Ok(self.last_id)
+    }
+
+    /// Start job `id` when it is the next queued one and a slot is free
+    fn start(&mut self, id: u64, concurrency: usize) -> bool {
+        let running = self
+            .list
+            .iter()
+            .filter(|entry| entry.job.status == JobStatus::Running)
+            .count();
+        let next = self
+            .list
+            .iter_mut()
+            .find(|entry| entry.job.status == JobStatus::Queued);
+        match next {
+            Some(entry) if entry.job.id == id && running < concurrency => {
+                entry.job.status = JobStatus::Running;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The running jobs first, then the queued ones in the order they start
+    fn jobs(&self) -> Vec<Job> {
+        let mut list: Vec<Job> = self.list.iter().map(|entry| entry.job.clone()).collect();
+        list.sort_by_key(|job| job.status != JobStatus::Running);
+        list
+    }
+}
+
+/// The place of a job, it leaves the list when dropped, whether it succeeded or not
+struct Ticket {
+    id: u64,
+
+    cancel: watch::Receiver<bool>,
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        lock().list.retain(|entry| entry.job.id != self.id);
+        changed();
+    }
+}
+
+fn lock() -> MutexGuard<'static, Jobs> {
+    JOBS.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Wake the queued jobs & report the list to the window
+fn changed() {
+    CHANGED.notify_waiters();
+    if let Some(window) = handle::Handle::global().get_window() {
+        let _ = window.emit("on-install-jobs", list());
+    }
+}
+
+/// Queue a job, wait for its turn, then run it with its id, cancel signal & download limit
+///
+/// At most `install_concurrency` jobs run at the same time.
+/// A job cancelled while queued never runs.
+/// `download_limit` (KB/s) applies to this job on top of the global `download_limit`.
+pub async fn run<T, F, Fut>(
+    kind: JobKind,
+    version: String,
+    arch: Option<String>,
+    download_limit: Option<u64>,
+    job: F,
+) -> Result<T>
+where
+    F: FnOnce(u64, watch::Receiver<bool>, RateLimit) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let (cancel_sender, cancel_receiver) = watch::channel(false);
+    let download_limit = download_limit.filter(|limit| *limit > 0);
+    let rate_limit = DOWNLOAD_LIMIT.child(download_limit.map(|limit| limit.saturating_mul(1024)));
+    let id = lock().queue(Entry {
+        job: Job {
+            id: 0,
+            kind,
+            version,
+            arch,
+            status: JobStatus::Queued,
+            download_limit,
+        },
+        cancel: cancel_sender,
+        download_limit: rate_limit.clone(),
+    })?;
+    let ticket = Ticket {
+        id,
+        cancel: cancel_receiver,
+    };
+    changed();
+
+    loop {
+        // created first, so that no change is missed while checking
+        let notified = CHANGED.notified();
+        {
+            if *ticket.cancel.borrow() {
+                bail!("Install was cancelled");
+            }
+            let concurrency = Config::settings().latest().get_install_concurrency();
+            if lock().start(ticket.id, concurrency) {
+                break;
+            }
+        }
+        notified.await;
+    }
+    changed();
+
+    job(ticket.id, ticket.cancel.clone(), rate_limit).await
+}
+
+/// The running jobs first, then the queued ones in the order they start
+pub fn list() -> Vec<Job> {
+    lock().jobs()
+}
+
+/// Cancel job `id`, every job when `None`
+pub fn cancel(id: Option<u64>) -> Result<()> {
+    {
+        let jobs = lock();
+        let mut found = false;
+        for entry in jobs
+            .list
+            .iter()
+            .filter(|entry| id.is_none() || id == Some(entry.job.id))
+        {
+            entry.cancel.send_replace(true);
+            found = true;
+        }
+        if let (Some(id), false) = (id, found) {
+            bail!("Install job {} not found", id);
+        }
+    }
+    changed();
+    Ok(())
+}
+
+/// Move queued job `id` to `position` in the queue, `0` to start it next
+pub fn reorder(id: u64, position: usize) -> Result<Vec<Job>> {
+    {
+        let mut jobs = lock();
+        let Some(index) = jobs.list.iter().position(|entry| entry.job.id == id) else {
+            bail!("Install job {} not found", id);
+        };
+        if jobs.list[index].job.status != JobStatus::Queued {
+            bail!("Install job {} is running already", id);
+        }
+
+        let entry = jobs.list.remove(index);
+        let target = jobs
+            .list
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.job.status == JobStatus::Queued)
+            .nth(position)
+            .map(|(index, _)| index)
+            .unwrap_or(jobs.list.len());
+        jobs.list.insert(target, entry);
+    }
+    changed();
+    Ok(list())
+}
+
+/// Limit the download bandwidth of job `id` (KB/s), `None` or `0` for no limit
+/// a running download slows down or speeds up right away
+pub fn limit(id: u64, download_limit: Option<u64>) -> Result<Vec<Job>> {
+    {
+        let mut jobs = lock();
+        let Some(entry) = jobs.list.iter_mut().find(|entry| entry.job.id == id) else {
+            bail!("Install job {} not found", id);
+        };
+        let download_limit = download_limit.filter(|limit| *limit > 0);
+        entry
+            .download_limit
+            .set(download_limit.map(|limit| limit.saturating_mul(1024)));
+        entry.job.download_limit = download_limit;
+    }
+    changed();
+    Ok(list())
+}
+
+/// Apply the settings to the jobs: start the queued ones that fit,
+/// e.g. after `install_concurrency` was raised, & update the global `download_limit`
+pub fn apply_settings() {
+    DOWNLOAD_LIMIT.set(Config::settings().latest().get_download_limit());
+    CHANGED.notify_waiters();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(kind: JobKind, version: &str, arch: Option<&str>) -> Entry {
+        Entry {
+            job: Job {
+                id: 0,
+                kind,
+                version: version.into(),
+                arch: arch.map(Into::into),
+                status: JobStatus::Queued,
+                download_limit: None,
+            },
+            cancel: watch::channel(false).0,
+            download_limit: RateLimit::default(),
+        }
+    }
+
+    #[test]
+    fn test_queue_same_version() {
+        let mut jobs = Jobs::default();
+        assert_eq!(
+            jobs.queue(entry(JobKind::Install, "20.11.1", None))
+                .unwrap(),
+            1
+        );
+
+        // the same archive, whether installed or downloaded into the cache
+        assert!(jobs
+            .queue(entry(JobKind::Install, "20.11.1", None))
+            .is_err());
+        assert!(jobs
+            .queue(entry(JobKind::Download, "20.11.1", None))
+            .is_err());
+        let arch = Platform::current().arch;
+        assert!(jobs
+            .queue(entry(JobKind::Install, "20.11.1", Some(&arch)))
+            .is_err());
+        assert_eq!(jobs.list.len(), 1);
+
+        // another version or arch
+        assert_eq!(
+            jobs.queue(entry(JobKind::Install, "22.1.0", None)).unwrap(),
+            2
+        );
+        let other = if arch == "x86" { "x64" } else { "x86" };
+        assert_eq!(
+            jobs.queue(entry(JobKind::Install, "20.11.1", Some(other)))
+                .unwrap(),
+            3
+        );
+
+        // queued again once done
+        jobs.list.retain(|entry| entry.job.id != 1);
+        assert_eq!(
+            jobs.queue(entry(JobKind::Install, "20.11.1", None))
+                .unwrap(),
+            4
+        );
+    }
+}
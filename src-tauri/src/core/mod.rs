@@ -1,6 +1,7 @@
 pub mod configuration;
 pub mod group;
 pub mod handle;
+pub mod jobs;
 pub mod node;
 pub mod project;
 pub mod tray;
@@ -5,31 +5,44 @@ use std::{
     time::Duration,
 };
 
-use anyhow::{anyhow, bail, Context, Result};
+use anyhow::{bail, Context, Result};
 use get_node::{
-    archive::{fetch_native, FetchConfig},
-    list::{version_list, ListConfig},
+    archive::{fetch_native, prefetch, FetchConfig, FetchResult},
+    cache::{self, CacheEntry},
+    janitor::{self, Leftover},
+    list::{release_list_if_modified, Fetched, IndexMeta, ListConfig},
+    mirror::{self, Benchmark, LastGood},
+    net::RateLimit,
+    platform::Platform,
+    progress::Progress,
+    release::Releases,
+    resolver::{self, Prefer, VersionSpec},
 };
-use once_cell::sync::Lazy;
-use serde::{Deserialize, Serialize};
+use node_semver::Version;
+use serde::Serialize;
 use tauri::Emitter;
 use tokio::{sync::watch, time::Instant};
 use version_compare::{compare, Cmp};
 
 use crate::{
     config::{Config, NVersion},
-    core::handle,
+    core::{
+        handle,
+        jobs::{self, Job, JobKind},
+    },
     log_err,
+    utils::{dirs, help},
 };
 
-static CANCEL_SENDER: Lazy<Arc<Mutex<Option<watch::Sender<bool>>>>> =
-    Lazy::new(|| Arc::new(Mutex::new(None)));
-
-#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+/// a progress event of install job `job`
+#[derive(Debug, Clone, Serialize)]
 pub struct ProgressData<'a> {
-    pub source: &'a str,
-    pub total: usize,
-    pub transferred: usize,
+    pub job: u64,
+
+    pub version: &'a str,
+
+    #[serde(flatten)]
+    pub progress: &'a Progress,
 }
 
 /// Get the currently set node version
@@ -46,11 +59,49 @@ pub fn get_current(fetch: Option<bool>) -> Result<Option<String>> {
     Ok(Config::node().latest().get_current())
 }
 
+/// resolve a version specifier (`lts/*`, `lts/iron`, `^18`, `20`, `latest` ...)
+/// to a concrete version from the installed versions and the version list
+pub async fn resolve_version(spec: &str, prefer: Prefer) -> Result<String> {
+    // exact versions are used as is, no need for the lists
+    if let VersionSpec::Exact(version) = spec.parse::<VersionSpec>()? {
+        return Ok(version.to_string());
+    }
+
+    let mut list = Config::node().latest().get_list().unwrap_or_default();
+    if list.is_empty() {
+        list = fetch_version_list().await?;
+    }
+    let installed = Config::node()
+        .latest()
+        .get_installed()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|version| Version::parse(version.trim_start_matches('v')).ok())
+        .collect::<Vec<_>>();
+
+    let version = resolver::resolve(spec, &Releases::from(list), &installed, prefer)?;
+
+    Ok(version.to_string())
+}
+
+/// resolve `spec` like `resolve_version`, an empty version clears it and is kept as is
+pub async fn resolve_optional_version(spec: &str, prefer: Prefer) -> Result<String> {
+    if spec.is_empty() {
+        return Ok(String::new());
+    }
+
+    resolve_version(spec, prefer).await
+}
+
 /// Set the current node version
-pub async fn set_current(version: Option<String>) -> Result<()> {
-    let version = version.as_deref().unwrap_or("");
+/// `version` may be a specifier, resolved from the installed versions first by default
+pub async fn set_current(version: Option<String>, prefer: Option<Prefer>) -> Result<()> {
+    let version = match version.as_deref().filter(|version| !version.is_empty()) {
+        Some(spec) => resolve_version(spec, prefer.unwrap_or(Prefer::Installed)).await?,
+        None => String::new(),
+    };
 
-    Config::node().draft().update_current(version)?;
+    Config::node().draft().update_current(&version)?;
     Config::node().apply();
     Config::node().data().save_current()?;
 
@@ -84,32 +135,128 @@ pub async fn update_current_from_menu(current: String) -> Result<()> {
     }
 }
 
-/// fetch version list data from remote or local
-/// remote when fetch is `true`
+/// the version list, with where it came from and how old it is
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct VersionList {
+    pub list: Vec<NVersion>,
+
+    /// index base url of the mirror the list came from
+    pub mirror: Option<String>,
+
+    /// when the list was fetched or last revalidated (seconds since the unix epoch)
+    pub fetched_at: Option<u64>,
+
+    /// seconds since `fetched_at`
+    pub age: Option<u64>,
+
+    /// older than the `index_ttl` setting, or never fetched
+    pub stale: bool,
+}
+
+/// get version list data from remote or local
+/// remote when fetch is `true`, only downloaded when it changed since the last fetch
 /// local when fetch is `false`
-pub async fn get_version_list(fetch: Option<bool>) -> Result<Option<Vec<NVersion>>> {
-    let fetch = fetch.unwrap_or(false);
-    if !fetch {
-        // return existing data directly
-        return Ok(Config::node().latest().get_list());
+pub async fn get_version_list(fetch: Option<bool>) -> Result<VersionList> {
+    if fetch.unwrap_or(false) {
+        fetch_version_list().await?;
     }
+    Ok(version_list_info())
+}
 
-    let settings = Config::settings().data().clone();
+/// the local version list & its metadata
+fn version_list_info() -> VersionList {
+    let ttl = Config::settings().latest().get_index_ttl();
+    let (list, meta) = {
+        let node = Config::node().latest();
+        (node.get_list().unwrap_or_default(), node.get_meta())
+    };
 
-    // fetch list data from remote
-    let list = version_list::<Vec<NVersion>>(ListConfig {
-        mirror: settings.mirror,
-        proxy: settings.proxy,
-        no_proxy: settings.no_proxy,
-        timeout: None,
-    })
+    let age = meta.as_ref().map(IndexMeta::age);
+    VersionList {
+        stale: list.is_empty() || age.map_or(true, |age| age >= ttl),
+        mirror: meta.as_ref().map(|meta| meta.mirror.clone()),
+        fetched_at: meta.map(|meta| meta.fetched_at),
+        age,
+        list,
+    }
+}
+
+/// fetch the version list from remote, unless it did not change since the last fetch
+async fn fetch_version_list() -> Result<Vec<NVersion>> {
+    let settings = Config::settings().data().clone();
+    let (list, cached) = {
+        let node = Config::node().latest();
+        (node.get_list().unwrap_or_default(), node.get_meta())
+    };
+    // the validators are only worth sending along with a list to keep
+    let cached = cached.filter(|_| !list.is_empty());
+
+    let fetched = release_list_if_modified(
+        ListConfig {
+            mirrors: settings.get_mirrors(),
+            unofficial: settings.get_unofficial_mirrors(),
+            proxy: settings.get_proxy(),
+            no_proxy: settings.no_proxy,
+            tls: settings.tls.clone(),
+            timeouts: Some(settings.get_timeouts()),
+            retry: Some(settings.get_retry()),
+            transport: None,
+        },
+        cached.as_ref(),
+    )
     .await?;
 
-    // update list
-    Config::node().draft().update_list(&list)?;
+    let list = match fetched {
+        Fetched::Modified(releases, meta) => {
+            let list = releases.into_vec();
+            Config::node().draft().update_list(&list)?;
+            Config::node().draft().update_meta(&meta)?;
+            list
+        }
+        Fetched::NotModified(meta) => {
+            Config::node().draft().update_meta(&meta)?;
+            list
+        }
+    };
     Config::node().apply();
+    log_err!(save_last_good_mirror());
+
+    Ok(list)
+}
+
+/// remember the mirrors that served the last requests, so that they are tried first after a restart
+fn save_last_good_mirror() -> Result<()> {
+    let path = dirs::last_good_mirror_path()?;
+    let saved = help::read_json::<LastGood>(&path).unwrap_or_default();
+    let current = LastGood::of(&Config::settings().latest().get_mirrors());
+    // an endpoint not requested in this run keeps the mirror of the previous ones
+    let updated = LastGood {
+        index: current.index.or_else(|| saved.index.clone()),
+        download: current.download.or_else(|| saved.download.clone()),
+    };
+    if updated != saved {
+        help::save_json(&path, &updated, None)?;
+    }
+    Ok(())
+}
 
-    Ok(Some(list))
+/// try the mirrors that served the last requests of the previous run first
+pub fn restore_last_good_mirror() -> Result<()> {
+    let path = dirs::last_good_mirror_path()?;
+    if path.exists() {
+        help::read_json::<LastGood>(&path)?.restore();
+    }
+    Ok(())
+}
+
+/// refresh the version list on launch once it is older than the `index_ttl` setting
+pub async fn refresh_version_list() -> Result<()> {
+    if !version_list_info().stale {
+        return Ok(());
+    }
+    let list = fetch_version_list().await?;
+    log::info!(target: "app", "version list refreshed, {} versions", list.len());
+    Ok(())
 }
 
 /// get node installed list
@@ -162,70 +309,123 @@ pub async fn get_installed_list(fetch: Option<bool>) -> Result<Option<Vec<String
     Ok(Some(versions))
 }
 
-/// install node
-pub async fn install_node(
+/// create the fetch config of install job `job` from settings
+/// the progress is emitted to `window`, the fetch is cancelled by `cancel_signal`
+/// and its download is throttled by `rate_limit`
+fn fetch_config(
     window: tauri::Window,
-    version: Option<String>,
+    job: u64,
+    version: String,
     arch: Option<String>,
-) -> Result<String> {
-    if version.is_none() {
-        bail!("version should not be null");
-    }
-
-    let version: String = version.unwrap();
+    cancel_signal: watch::Receiver<bool>,
+    rate_limit: RateLimit,
+) -> Result<FetchConfig> {
     let settings = Config::settings().latest().clone();
-    let mirror = settings.mirror.unwrap();
+    let cache_dir = settings.get_cache_dir();
+    let mirrors = settings.get_mirrors();
     let directory = settings.directory.unwrap();
 
     let last_emit_time = Arc::new(Mutex::new(Instant::now()));
 
-    let (cancel_sender, cancel_receiver) = watch::channel(false);
-    {
-        let mut sender_lock = CANCEL_SENDER.lock().unwrap();
-        *sender_lock = Some(cancel_sender);
-    }
-
-    let config = FetchConfig {
+    Ok(FetchConfig {
         dest: directory,
-        mirror: mirror,
-        arch,
-        version: version,
+        mirrors,
+        unofficial: settings.get_unofficial_mirrors(),
+        platform: Some(Platform::current().with_arch(arch)),
+        version: version.clone(),
         no_proxy: settings.no_proxy,
-        proxy: settings.proxy,
-        cancel_signal: Some(cancel_receiver),
-        timeout: None,
+        tls: settings.tls.clone(),
+        proxy: settings.get_proxy(),
+        cancel_signal: Some(cancel_signal),
+        timeouts: Some(settings.get_timeouts()),
+        retry: Some(settings.get_retry()),
+        transport: None,
+        signature: settings.signature,
+        cache_dir,
+        streaming: settings.enable_streaming,
+        rate_limit: Some(rate_limit),
         on_progress: Box::new({
-            move |source: &str, transferred: usize, total: usize| {
+            move |progress: &Progress| {
                 let mut last_emit_time = last_emit_time.lock().unwrap();
                 let now = Instant::now();
-                if now.duration_since(*last_emit_time) >= Duration::from_millis(300) {
+                // every phase change is reported, the transfers at most every 300ms
+                let transfer =
+                    matches!(progress, Progress::Downloading(_) | Progress::Extracting(_));
+                if !transfer || now.duration_since(*last_emit_time) >= Duration::from_millis(300) {
                     *last_emit_time = now;
                     let _ = window.emit(
                         "on-node-progress",
                         ProgressData {
-                            source,
-                            transferred,
-                            total,
+                            job,
+                            version: &version,
+                            progress,
                         },
                     );
                 }
             }
         }),
+    })
+}
+
+/// install node
+/// `version` may be a specifier, resolved from the version list first by default
+/// the install waits in the queue while `install_concurrency` installs are running
+/// `download_limit` (KB/s) limits its download on top of the `download_limit` setting
+pub async fn install_node(
+    window: tauri::Window,
+    version: Option<String>,
+    arch: Option<String>,
+    prefer: Option<Prefer>,
+    download_limit: Option<u64>,
+) -> Result<FetchResult> {
+    let Some(spec) = version else {
+        bail!("version should not be null");
     };
+    let version = resolve_version(&spec, prefer.unwrap_or(Prefer::Remote)).await?;
+    let ret = jobs::run(
+        JobKind::Install,
+        version.clone(),
+        arch.clone(),
+        download_limit,
+        |job, cancel_signal, rate_limit| async move {
+            fetch_native(fetch_config(
+                window,
+                job,
+                version,
+                arch,
+                cancel_signal,
+                rate_limit,
+            )?)
+            .await
+        },
+    )
+    .await?;
 
-    fetch_native(config).await
+    log_err!(save_last_good_mirror());
+    // keep the archive cache within its size limit
+    log_err!(cache_prune(None).await);
+
+    Ok(ret)
 }
 
-/// cancel install node
-pub async fn install_node_cancel() -> Result<()> {
-    if let Some(sender) = CANCEL_SENDER
-        .lock()
-        .map_err(|err| anyhow!(err.to_string()))?
-        .as_ref()
-    {
-        let _ = sender.send(true);
-    }
-    Ok(())
+/// cancel install job `id`, every install when `None`
+pub async fn install_node_cancel(id: Option<u64>) -> Result<()> {
+    jobs::cancel(id)
+}
+
+/// the installs running & waiting in the queue
+pub async fn install_jobs() -> Result<Vec<Job>> {
+    Ok(jobs::list())
+}
+
+/// move the queued install `id` to `position` in the queue, `0` to start it next
+pub async fn install_job_move(id: u64, position: usize) -> Result<Vec<Job>> {
+    jobs::reorder(id, position)
+}
+
+/// limit the download bandwidth of install `id` (KB/s), `None` or `0` for no limit
+pub async fn install_job_limit(id: u64, download_limit: Option<u64>) -> Result<Vec<Job>> {
+    jobs::limit(id, download_limit)
 }
 
 /// uninstall node
@@ -243,3 +443,165 @@ pub async fn uninstall_node(version: String) -> Result<()> {
 
     Ok(())
 }
+
+/// find what interrupted installs left in the install directory
+///
+/// The staging directories & downloads of the jobs queued or running are not leftovers,
+/// so they are never offered to be removed or finished while in use.
+pub async fn leftover_list() -> Result<Vec<Leftover>> {
+    let Some(directory) = Config::settings().latest().get_directory() else {
+        return Ok(vec![]);
+    };
+    let leftovers = janitor::scan(&PathBuf::from(directory)).await?;
+
+    let active = jobs::list()
+        .into_iter()
+        .map(|job| job.version.trim_start_matches('v').to_string())
+        .collect::<Vec<_>>();
+    Ok(leftovers
+        .into_iter()
+        .filter(|leftover| {
+            leftover
+                .version
+                .as_deref()
+                .is_none_or(|version| !active.iter().any(|active| active == version))
+        })
+        .collect())
+}
+
+/// remove the leftovers at `paths`
+pub async fn leftover_clean(paths: Vec<String>) -> Result<Vec<Leftover>> {
+    // only what the scan found is removed, whatever the paths are
+    for leftover in leftover_list().await? {
+        if paths.contains(&leftover.path) {
+            janitor::clean(&leftover)
+                .await
+                .context(format!("Failed to remove leftover: {:?}", leftover.path))?;
+        }
+    }
+
+    leftover_list().await
+}
+
+/// finish the installs of the unrenamed leftovers at `paths`
+pub async fn leftover_finish(paths: Vec<String>) -> Result<Vec<Leftover>> {
+    for leftover in leftover_list().await? {
+        if paths.contains(&leftover.path) {
+            janitor::finish(&leftover).await?;
+        }
+    }
+
+    leftover_list().await
+}
+
+/// scan the install directory at startup, the leftovers found are reported to the window
+pub async fn leftover_check() -> Result<()> {
+    let leftovers = leftover_list().await?;
+    if leftovers.is_empty() {
+        return Ok(());
+    }
+
+    let size: u64 = leftovers.iter().map(|leftover| leftover.size).sum();
+    log::warn!(
+        target: "app",
+        "{} leftovers of interrupted installs found ({} bytes)",
+        leftovers.len(),
+        size
+    );
+    if let Some(window) = handle::Handle::global().get_window() {
+        window.emit("on-leftovers", &leftovers)?;
+    }
+    Ok(())
+}
+
+/// fetch the version index from every mirror, the fastest first
+pub async fn mirror_benchmark() -> Result<Vec<Benchmark>> {
+    let settings = Config::settings().latest().clone();
+
+    mirror::benchmark(ListConfig {
+        mirrors: settings.get_mirrors(),
+        unofficial: settings.get_unofficial_mirrors(),
+        proxy: settings.get_proxy(),
+        no_proxy: settings.no_proxy,
+        tls: settings.tls.clone(),
+        timeouts: Some(settings.get_timeouts()),
+        retry: Some(settings.get_retry()),
+        transport: None,
+    })
+    .await
+}
+
+/// the proxy a request to `url` goes through with the current settings, `None` when direct
+pub async fn proxy_for(url: String) -> Result<Option<String>> {
+    let settings = Config::settings().latest().clone();
+
+    get_node::proxy::proxy_for(settings.get_proxy().as_ref(), settings.no_proxy, &url).await
+}
+
+/// get the archives in the cache
+pub async fn cache_list() -> Result<Vec<CacheEntry>> {
+    cache::list(&dirs::cache_dir()?).await
+}
+
+/// download the node archive into the cache without installing it
+pub async fn cache_download(
+    window: tauri::Window,
+    version: Option<String>,
+    arch: Option<String>,
+    download_limit: Option<u64>,
+) -> Result<CacheEntry> {
+    let Some(version) = version else {
+        bail!("version should not be null");
+    };
+    let entry = jobs::run(
+        JobKind::Download,
+        version.clone(),
+        arch.clone(),
+        download_limit,
+        |job, cancel_signal, rate_limit| async move {
+            let mut config = fetch_config(window, job, version, arch, cancel_signal, rate_limit)?;
+            // pre-download even if installs do not use the cache
+            config.cache_dir = Some(dirs::cache_dir()?.to_string_lossy().to_string());
+            prefetch(config).await
+        },
+    )
+    .await?;
+
+    log_err!(save_last_good_mirror());
+    log_err!(cache_prune(None).await);
+
+    Ok(entry)
+}
+
+/// remove the least recently used archives until the cache is not larger than `max_size` (MB)
+/// `max_size` defaults to the `cache_limit` setting, `0` clears the cache
+pub async fn cache_prune(max_size: Option<u64>) -> Result<Vec<CacheEntry>> {
+    let max_size = match max_size {
+        Some(max_size) => max_size.saturating_mul(1024 * 1024),
+        None => Config::settings().latest().get_cache_limit(),
+    };
+
+    cache::prune(&dirs::cache_dir()?, max_size).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_optional_version() {
+        // a cleared version, e.g. a project removed from its group
+        assert_eq!(
+            resolve_optional_version("", Prefer::Installed)
+                .await
+                .unwrap(),
+            ""
+        );
+        assert_eq!(
+            resolve_optional_version("20.11.1", Prefer::Installed)
+                .await
+                .unwrap(),
+            "20.11.1"
+        );
+    }
+}
@@ -7,10 +7,11 @@ use crate::{
 };
 use anyhow::{anyhow, Result};
 use futures::{stream, StreamExt};
+use get_node::resolver::Prefer;
 use serde::{Deserialize, Serialize};
 use tauri_plugin_dialog::{DialogExt, FilePath};
 
-use super::handle;
+use super::{handle, node::resolve_optional_version};
 
 /// get project list from `projects.json`
 pub async fn project_list(fetch: Option<bool>) -> Result<Option<Vec<Project>>> {
@@ -78,19 +79,31 @@ pub async fn update_projects(list: Vec<Project>, path: Option<PathBuf>) -> Resul
 }
 
 /// sync project version to `.nvmdrc`
-pub async fn sync_project_version(path: PathBuf, version: &str) -> Result<i32> {
+/// `version` may be a specifier, resolved from the installed versions first by default
+pub async fn sync_project_version(
+    path: PathBuf,
+    version: &str,
+    prefer: Option<Prefer>,
+) -> Result<i32> {
     if !path.exists() {
         return Ok(404);
     }
 
+    let version = resolve_optional_version(version, prefer.unwrap_or_default()).await?;
     let path = path.join(".nvmdrc");
-    help::async_save_string(&path, version).await?;
+    help::async_save_string(&path, &version).await?;
 
     Ok(200)
 }
 
 /// batch update project version
-pub async fn batch_update_project_version(paths: Vec<PathBuf>, version: String) -> Result<()> {
+/// `version` may be a specifier, resolved from the installed versions first by default
+pub async fn batch_update_project_version(
+    paths: Vec<PathBuf>,
+    version: String,
+    prefer: Option<Prefer>,
+) -> Result<()> {
+    let version = resolve_optional_version(&version, prefer.unwrap_or_default()).await?;
     let result = stream::iter(paths.into_iter())
         .map(|path| {
             let version = version.clone();
@@ -116,7 +129,7 @@ pub async fn change_with_version(name: String, version: String) -> Result<()> {
         let project_path = Config::projects().draft().update_version(&name, &version)?;
         let need_update_groups = Config::groups().draft().update_projects(&project_path)?;
 
-        sync_project_version(PathBuf::from(&project_path), &version).await?;
+        sync_project_version(PathBuf::from(&project_path), &version, None).await?;
 
         log_err!(handle::Handle::update_systray_part_with_emit(
             "call-projects-update",
@@ -157,7 +170,7 @@ pub async fn change_with_group(name: String, group_name: String) -> Result<()> {
             .update_projects_version(&project_path, &group_name)?
             .ok_or_else(|| anyhow!("failed to find the group version \"name:{}\"", &group_name))?;
 
-        sync_project_version(PathBuf::from(&project_path), &version).await?;
+        sync_project_version(PathBuf::from(&project_path), &version, None).await?;
 
         log_err!(handle::Handle::update_systray_part_with_emit(
             "call-projects-update",
@@ -43,11 +43,25 @@ fn main() -> tauri::Result<()> {
             // node
             cmds::current,
             cmds::set_current,
+            cmds::resolve_version,
             cmds::version_list,
             cmds::installed_list,
             cmds::install_node,
             cmds::uninstall_node,
             cmds::install_node_cancel,
+            cmds::install_jobs,
+            cmds::install_job_move,
+            cmds::install_job_limit,
+            cmds::mirror_benchmark,
+            cmds::proxy_for,
+            // leftovers of interrupted installs
+            cmds::leftover_list,
+            cmds::leftover_clean,
+            cmds::leftover_finish,
+            // archive cache
+            cmds::cache_list,
+            cmds::cache_download,
+            cmds::cache_prune,
             // projects
             cmds::project_list,
             cmds::select_projects,
@@ -0,0 +1,111 @@
+use std::{collections::BTreeMap, fs};
+
+use anyhow::{Context, Result};
+use get_node::mirror::{Credentials, Mirror};
+use keyring::Entry;
+
+use super::{dirs, help};
+
+/// The service the secrets are saved under in the system keychain
+static SERVICE: &str = "nvm-desktop";
+
+/// The keychain account of the proxy password
+pub static PROXY_ACCOUNT: &str = "proxy";
+
+/// get a secret from the system keychain, `None` when there is none
+pub fn get_secret(account: &str) -> Result<Option<String>> {
+    match Entry::new(SERVICE, account)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// save a secret to the system keychain, an empty one removes it
+pub fn set_secret(account: &str, secret: &str) -> Result<()> {
+    let entry = Entry::new(SERVICE, account)?;
+    if !secret.is_empty() {
+        entry.set_password(secret)?;
+        return Ok(());
+    }
+
+    match entry.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// The credentials of the private mirrors, by mirror url
+pub type MirrorCredentials = BTreeMap<String, Credentials>;
+
+/// The keychain account of the mirror credentials, saved as one json object
+pub static MIRRORS_ACCOUNT: &str = "mirrors";
+
+/// the key of a mirror in the saved credentials
+pub fn mirror_key(url: &str) -> String {
+    url.trim().trim_end_matches('/').to_string()
+}
+
+/// read the mirror credentials from the system keychain
+/// those left in `credentials.json` by an older version are moved there first
+pub fn read_mirror_credentials() -> Result<MirrorCredentials> {
+    migrate_mirror_credentials()?;
+    match get_secret(MIRRORS_ACCOUNT)? {
+        Some(json) => serde_json::from_str(&json).context("failed to parse the mirror credentials"),
+        None => Ok(MirrorCredentials::new()),
+    }
+}
+
+/// save the mirror credentials to the system keychain, none removes the entry
+pub fn save_mirror_credentials(credentials: &MirrorCredentials) -> Result<()> {
+    let json = match credentials.is_empty() {
+        true => String::new(),
+        false => serde_json::to_string(credentials)?,
+    };
+    set_secret(MIRRORS_ACCOUNT, &json)
+}
+
+/// move the plaintext `credentials.json` of an older version into the keychain
+fn migrate_mirror_credentials() -> Result<()> {
+    let path = dirs::credentials_path()?;
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let legacy: MirrorCredentials = help::read_json(&path)?;
+    if !legacy.is_empty() {
+        let mut saved = match get_secret(MIRRORS_ACCOUNT)? {
+            Some(json) => serde_json::from_str(&json).unwrap_or_default(),
+            None => MirrorCredentials::new(),
+        };
+        // the ones saved to the keychain since are newer
+        for (key, credentials) in legacy {
+            saved.entry(key).or_insert(credentials);
+        }
+        save_mirror_credentials(&saved)?;
+    }
+    fs::remove_file(&path).with_context(|| format!("failed to remove file \"{}\"", path.display()))
+}
+
+/// save the credentials set on `mirrors`, empty ones are removed
+/// the mirrors without credentials keep the saved ones, those of the other mirrors are dropped
+pub fn update_mirror_credentials(mirrors: &[Mirror]) -> Result<()> {
+    let saved = read_mirror_credentials()?;
+    let mut updated = MirrorCredentials::new();
+    for mirror in mirrors {
+        let key = mirror_key(&mirror.url);
+        let credentials = match &mirror.credentials {
+            Some(credentials) if credentials.is_empty() => None,
+            Some(credentials) => Some(credentials.clone()),
+            None => saved.get(&key).cloned(),
+        };
+        if let Some(credentials) = credentials {
+            updated.insert(key, credentials);
+        }
+    }
+
+    if updated != saved {
+        save_mirror_credentials(&updated)?;
+    }
+    Ok(())
+}
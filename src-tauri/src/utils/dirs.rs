@@ -19,6 +19,11 @@ pub fn settings_path() -> Result<PathBuf> {
     Ok(nvmd_home_dir()?.join("setting.json"))
 }
 
+/// get the `credentials.json` file path, where older versions kept the mirror secrets
+pub fn credentials_path() -> Result<PathBuf> {
+    Ok(nvmd_home_dir()?.join("credentials.json"))
+}
+
 /// get the `projects.json` file path
 pub fn projects_path() -> Result<PathBuf> {
     Ok(nvmd_home_dir()?.join("projects.json"))
@@ -49,6 +54,21 @@ pub fn version_list_path() -> Result<PathBuf> {
     Ok(nvmd_home_dir()?.join("versions.json"))
 }
 
+/// get the version list metadata path (validators, fetch time & mirror)
+pub fn version_meta_path() -> Result<PathBuf> {
+    Ok(nvmd_home_dir()?.join("versions.meta.json"))
+}
+
+/// get the path of the mirrors that served the last requests, tried first after a restart
+pub fn last_good_mirror_path() -> Result<PathBuf> {
+    Ok(nvmd_home_dir()?.join("mirrors.last-good.json"))
+}
+
+/// get the archive cache dir
+pub fn cache_dir() -> Result<PathBuf> {
+    Ok(nvmd_home_dir()?.join("cache"))
+}
+
 /// get the default install directory
 pub fn default_install_dir() -> PathBuf {
     match nvmd_home_dir() {
@@ -1,3 +1,4 @@
+pub mod credentials;
 pub mod dirs;
 pub mod help;
 pub mod migrate;
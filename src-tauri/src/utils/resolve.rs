@@ -4,7 +4,7 @@ use tauri_plugin_window_state::{StateFlags, WindowExt};
 
 use crate::{
     config::Config,
-    core::{handle, tray},
+    core::{handle, node, tray},
     log_err, trace_err,
     utils::migrate,
 };
@@ -25,6 +25,19 @@ pub async fn resolve_setup(app: &mut App) {
     }
 
     log_err!(handle::Handle::update_systray_part());
+
+    // report what interrupted installs left behind
+    tauri::async_runtime::spawn(async {
+        log_err!(node::leftover_check().await);
+    });
+
+    // the mirrors that served the last requests are tried first
+    log_err!(node::restore_last_good_mirror());
+
+    // refresh the version list once it is older than the `index_ttl` setting
+    tauri::async_runtime::spawn(async {
+        log_err!(node::refresh_version_list().await);
+    });
 }
 
 /// create main window
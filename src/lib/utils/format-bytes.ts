@@ -0,0 +1,10 @@
+// e.g. `12.3 MB`
+export function formatBytes(bytes: number) {
+  const units = ['B', 'KB', 'MB', 'GB'];
+  let index = 0;
+  while (bytes >= 1024 && index < units.length - 1) {
+    bytes /= 1024;
+    index++;
+  }
+  return `${bytes.toFixed(index ? 1 : 0)} ${units[index]}`;
+}
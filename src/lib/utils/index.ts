@@ -1,4 +1,5 @@
 export * from './apply-theme';
 export * from './compare';
+export * from './format-bytes';
 export * from './get-system';
 export * from './ts-cn';
@@ -19,6 +19,7 @@ import {
 } from '@radix-ui/react-icons';
 import { ThemeCustomizer } from '@/components/theme-customizer';
 import { Updater } from './updater';
+import { Leftovers } from './leftovers';
 import { toast } from 'sonner';
 import { Steps } from 'intro.js-react';
 
@@ -584,6 +585,7 @@ const Home: React.FC = () => {
           setEnabled(false);
         }}
       />
+      <Leftovers />
     </>
   );
 };
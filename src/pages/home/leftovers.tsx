@@ -0,0 +1,127 @@
+import { useEffect, useState } from 'react';
+import {
+  AlertDialog,
+  AlertDialogCancel,
+  AlertDialogContent,
+  AlertDialogDescription,
+  AlertDialogFooter,
+  AlertDialogHeader,
+  AlertDialogTitle,
+  Button,
+} from '@/components/ui';
+
+import { toast } from 'sonner';
+import { useTranslation } from 'react-i18next';
+import { formatBytes } from '@/lib/utils';
+import { getCurrent } from '@/services/api';
+import { leftoverClean, leftoverFinish, leftoverList } from '@/services/cmds';
+
+export const Leftovers: React.FC = () => {
+  const [open, setOpen] = useState<boolean>(false);
+  const [loading, setLoading] = useState<boolean>(false);
+  const [leftovers, setLeftovers] = useState<Nvmd.Leftover[]>([]);
+
+  const { t } = useTranslation();
+
+  useEffect(() => {
+    const onLeftovers = (leftovers: Nvmd.Leftover[]) => {
+      setLeftovers(leftovers);
+      setOpen(leftovers.length > 0);
+    };
+
+    // the startup scan may be done before this page listens to `on-leftovers`
+    leftoverList().then(onLeftovers).catch(() => {});
+    const unlisted = getCurrent().listen<Nvmd.Leftover[]>(
+      'on-leftovers',
+      ({ payload }) => onLeftovers(payload),
+    );
+
+    return () => {
+      unlisted.then((fn) => fn());
+    };
+  }, []);
+
+  const onAction = async (action: () => Promise<Nvmd.Leftover[]>) => {
+    setLoading(true);
+    try {
+      const remaining = await action();
+      setLeftovers(remaining);
+      if (!remaining.length) {
+        setOpen(false);
+        toast.success(t('Leftovers-Done'));
+      }
+    } catch (err) {
+      toast.error(err?.message || err.toString());
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const size = leftovers.reduce((size, leftover) => size + leftover.size, 0);
+  const finishable = leftovers.filter((leftover) => leftover.finishable);
+
+  return (
+    <AlertDialog open={open}>
+      <AlertDialogContent className='top-72'>
+        <AlertDialogHeader>
+          <AlertDialogTitle>{t('Leftovers')}</AlertDialogTitle>
+          <AlertDialogDescription>
+            {t('Leftovers-Tip', { size: formatBytes(size) })}
+          </AlertDialogDescription>
+        </AlertDialogHeader>
+        <ul className='max-h-60 overflow-auto space-y-2'>
+          {leftovers.map((leftover) => (
+            <li key={leftover.path} className='text-sm'>
+              <p className='flex items-center gap-2'>
+                <span>{t(`Leftover-${leftover.kind}`)}</span>
+                {leftover.version && (
+                  <span className='text-muted-foreground'>
+                    v{leftover.version}
+                  </span>
+                )}
+                <span className='text-muted-foreground'>
+                  {formatBytes(leftover.size)}
+                </span>
+              </p>
+              <p className='text-xs text-muted-foreground break-all'>
+                {leftover.path}
+              </p>
+            </li>
+          ))}
+        </ul>
+        <AlertDialogFooter>
+          <AlertDialogCancel
+            disabled={loading}
+            onClick={() => setOpen(false)}
+          >
+            {t('Cancel')}
+          </AlertDialogCancel>
+          {finishable.length > 0 && (
+            <Button
+              variant='secondary'
+              loading={loading}
+              onClick={() =>
+                onAction(() =>
+                  leftoverFinish(finishable.map((leftover) => leftover.path)),
+                )
+              }
+            >
+              {t('Leftovers-Finish')}
+            </Button>
+          )}
+          <Button
+            variant='destructive'
+            loading={loading}
+            onClick={() =>
+              onAction(() =>
+                leftoverClean(leftovers.map((leftover) => leftover.path)),
+              )
+            }
+          >
+            {t('Leftovers-Clean')}
+          </Button>
+        </AlertDialogFooter>
+      </AlertDialogContent>
+    </AlertDialog>
+  );
+};
@@ -44,31 +44,66 @@ import { useForm } from 'react-hook-form';
 import { useTranslation } from 'react-i18next';
 import { zodResolver } from '@hookform/resolvers/zod';
 import { useAppContext } from '@/app-context';
+import { toast } from 'sonner';
 import { compareObject } from '@/lib/utils';
+import { proxyFor } from '@/services/cmds';
 import { Closer, Themes } from '@/types';
 
 type Options = NonNullable<AutoCompleteProps['options']>;
 
 type Props = unknown;
 
+// a url, or the absolute path of a local mirror directory (USB stick, NFS share)
+const isMirror = (value: string) => {
+  if (/^(\/|[a-zA-Z]:[\\/]|\\\\)/.test(value)) return true;
+  try {
+    new URL(value);
+    return true;
+  } catch {
+    return false;
+  }
+};
+
+const schemes = ['http', 'https', 'socks5', 'socks5h'] as const;
+
+const modes = ['manual', 'system', 'pac'] as const;
+
 const formSchema = z.object({
   locale: z.string(),
   theme: z.nativeEnum(Themes),
   closer: z.nativeEnum(Closer),
   coder: z.string(),
   directory: z.string().min(1),
-  mirror: z.string().url({ message: 'Invalid mirror url' }),
+  mirror: z.string().refine(isMirror, { message: 'Invalid mirror url' }),
   proxy: z
     .object({
       enabled: z.boolean().default(false),
+      mode: z.enum(modes).default('manual'),
+      scheme: z.enum(schemes).default('http'),
       ip: z.string().ip({ message: 'Invalid ip' }).optional().or(z.literal('')),
       port: z
         .string()
         .regex(/^\d+$/, 'Invalid port')
         .optional()
         .or(z.literal('')),
+      username: z.string().optional(),
+      // empty to keep the password saved in the system keychain
+      password: z.string().optional(),
+      // comma separated in the form
+      bypass: z.string().optional(),
+      pac: z.string().optional(),
     })
     .superRefine((val, ctx) => {
+      if (val.enabled && val.mode === 'pac' && !val.pac?.trim()) {
+        ctx.addIssue({
+          code: z.ZodIssueCode.custom,
+          path: ['pac'],
+          message: 'Invalid PAC file',
+        });
+      }
+
+      if (val.enabled && val.mode !== 'manual') return;
+
       if (val.enabled && (val.ip === '' || val.ip === void 0)) {
         ctx.addIssue({
           code: z.ZodIssueCode.custom,
@@ -90,9 +125,46 @@ const formSchema = z.object({
     }),
 });
 
+type ProxyValues = z.infer<typeof formSchema>['proxy'];
+
+// the saved proxy as edited in the form, the password is never read back
+const toProxyValues = (proxy?: Nvmd.Proxy): ProxyValues => ({
+  enabled: proxy?.enabled ?? false,
+  mode: proxy?.mode ?? 'manual',
+  scheme: proxy?.scheme ?? 'http',
+  ip: proxy?.ip ?? '',
+  port: proxy?.port ?? '',
+  username: proxy?.username ?? '',
+  password: '',
+  bypass: proxy?.bypass?.join(', ') ?? '',
+  pac: proxy?.pac ?? '',
+});
+
+// the fields the form does not edit are kept
+const toProxy = (
+  proxy: Nvmd.Proxy | undefined,
+  { password, bypass, ...values }: ProxyValues,
+): Nvmd.Proxy => {
+  const newProxy: Nvmd.Proxy = {
+    ...proxy,
+    ...values,
+    bypass: bypass?.split(/[\s,]+/).filter(Boolean) ?? [],
+  };
+  if (password) {
+    newProxy.password = password;
+  } else if (proxy?.username && !values.username) {
+    // nothing to authenticate with anymore, the saved password is removed
+    newProxy.password = '';
+  }
+  return newProxy;
+};
+
 const Setting: React.FC<Props> = () => {
   const [open, setOpen] = useState<boolean>(false);
   const [loading, setLoading] = useState<boolean>(false);
+  const [checking, setChecking] = useState<boolean>(false);
+  // the proxy of the requests to the mirror, `null` when connecting directly
+  const [usedProxy, setUsedProxy] = useState<string | null>();
 
   const [options, setOptions] = useState<Options>(() => {
     const optStr = localStorage.getItem('nvmd-mirror');
@@ -102,7 +174,7 @@ const Setting: React.FC<Props> = () => {
   const { settings, updateSetting } = useAppContext();
   const defaultSettings = {
     ...settings,
-    proxy: settings.proxy || { enabled: false, ip: '', port: '' },
+    proxy: toProxyValues(settings.proxy),
   };
 
   const form = useForm<z.infer<typeof formSchema>>({
@@ -112,6 +184,18 @@ const Setting: React.FC<Props> = () => {
 
   const { t } = useTranslation();
 
+  // with the saved settings
+  const onCheckProxy = async () => {
+    setChecking(true);
+    try {
+      setUsedProxy(await proxyFor(settings.mirror));
+    } catch (err) {
+      toast.error(err?.message || err.toString());
+    } finally {
+      setChecking(false);
+    }
+  };
+
   const onSubmit = async (values: z.infer<typeof formSchema>) => {
     setLoading(true);
     const {
@@ -121,8 +205,9 @@ const Setting: React.FC<Props> = () => {
       coder: newCoder,
       directory: newDirectory,
       mirror: newMirror,
-      proxy: newProxy,
+      proxy: proxyValues,
     } = values;
+    const newProxy = toProxy(settings.proxy, proxyValues);
     if (
       settings.locale === newLocale &&
       settings.theme === newTheme &&
@@ -130,7 +215,7 @@ const Setting: React.FC<Props> = () => {
       settings.coder === newCoder &&
       settings.directory === newDirectory &&
       settings.mirror === newMirror &&
-      compareObject(settings.proxy, newProxy)
+      compareObject(toProxyValues(settings.proxy), proxyValues)
     ) {
       setLoading(false);
       setOpen(false);
@@ -386,7 +471,7 @@ const Setting: React.FC<Props> = () => {
               control={form.control}
               name='proxy'
               render={({ field }) => {
-                const { enabled } = field.value;
+                const { enabled, mode } = field.value;
 
                 return (
                   <FormItem>
@@ -411,36 +496,193 @@ const Setting: React.FC<Props> = () => {
                           </FormItem>
                         )}
                       />
-                      <div className='flex items-center gap-2'>
+                      <FormField
+                        control={form.control}
+                        name='proxy.mode'
+                        render={({ field }) => (
+                          <FormItem>
+                            <FormControl>
+                              <RadioGroup
+                                disabled={!enabled}
+                                onValueChange={field.onChange}
+                                value={field.value}
+                                className='flex space-x-1'
+                              >
+                                {modes.map((mode) => (
+                                  <FormItem
+                                    key={mode}
+                                    className='flex items-center space-x-3 space-y-0'
+                                  >
+                                    <FormControl>
+                                      <RadioGroupItem value={mode} />
+                                    </FormControl>
+                                    <FormLabel className='font-normal'>
+                                      {t(`Proxy-${mode}`)}
+                                    </FormLabel>
+                                  </FormItem>
+                                ))}
+                              </RadioGroup>
+                            </FormControl>
+                          </FormItem>
+                        )}
+                      />
+                      {mode === 'manual' && (
+                        <>
+                          <div className='flex items-center gap-2'>
+                            <FormField
+                              control={form.control}
+                              name='proxy.scheme'
+                              render={({ field }) => (
+                                <FormItem>
+                                  <Select
+                                    disabled={!enabled}
+                                    onValueChange={field.onChange}
+                                    value={field.value}
+                                  >
+                                    <FormControl>
+                                      <SelectTrigger className='w-24 h-8'>
+                                        <SelectValue />
+                                      </SelectTrigger>
+                                    </FormControl>
+                                    <SelectContent>
+                                      {schemes.map((scheme) => (
+                                        <SelectItem key={scheme} value={scheme}>
+                                          {scheme}
+                                        </SelectItem>
+                                      ))}
+                                    </SelectContent>
+                                  </Select>
+                                </FormItem>
+                              )}
+                            />
+                            <FormField
+                              control={form.control}
+                              name='proxy.ip'
+                              render={({ field }) => (
+                                <FormItem>
+                                  <FormControl>
+                                    <IpInput disabled={!enabled} {...field} />
+                                  </FormControl>
+                                  <FormMessage className='absolute !mt-1' />
+                                </FormItem>
+                              )}
+                            />
+                            <span>:</span>
+                            <FormField
+                              control={form.control}
+                              name='proxy.port'
+                              render={({ field }) => (
+                                <FormItem>
+                                  <FormControl>
+                                    <Input
+                                      className='w-[72px] h-8 text-center'
+                                      disabled={!enabled}
+                                      {...field}
+                                    />
+                                  </FormControl>
+                                  <FormMessage className='absolute !mt-1' />
+                                </FormItem>
+                              )}
+                            />
+                          </div>
+                          <div className='flex items-center gap-2'>
+                            <FormField
+                              control={form.control}
+                              name='proxy.username'
+                              render={({ field }) => (
+                                <FormItem>
+                                  <FormControl>
+                                    <Input
+                                      className='h-8'
+                                      autoComplete='off'
+                                      placeholder={t('Proxy-Username')}
+                                      disabled={!enabled}
+                                      {...field}
+                                    />
+                                  </FormControl>
+                                </FormItem>
+                              )}
+                            />
+                            <FormField
+                              control={form.control}
+                              name='proxy.password'
+                              render={({ field }) => (
+                                <FormItem>
+                                  <FormControl>
+                                    <Input
+                                      className='h-8'
+                                      type='password'
+                                      autoComplete='new-password'
+                                      placeholder={t('Proxy-Password')}
+                                      disabled={!enabled}
+                                      {...field}
+                                    />
+                                  </FormControl>
+                                </FormItem>
+                              )}
+                            />
+                          </div>
+                        </>
+                      )}
+                      {mode === 'pac' && (
                         <FormField
                           control={form.control}
-                          name='proxy.ip'
+                          name='proxy.pac'
                           render={({ field }) => (
                             <FormItem>
                               <FormControl>
-                                <IpInput disabled={!enabled} {...field} />
+                                <Input
+                                  className='h-8'
+                                  placeholder='http://wpad.corp/proxy.pac'
+                                  disabled={!enabled}
+                                  {...field}
+                                />
                               </FormControl>
-                              <FormMessage className='absolute !mt-1' />
+                              <FormDescription>
+                                {t('Proxy-Pac-Tip')}
+                              </FormDescription>
+                              <FormMessage />
                             </FormItem>
                           )}
                         />
-                        <span>:</span>
+                      )}
+                      {mode !== 'system' && (
                         <FormField
                           control={form.control}
-                          name='proxy.port'
+                          name='proxy.bypass'
                           render={({ field }) => (
                             <FormItem>
                               <FormControl>
                                 <Input
-                                  className='w-[72px] h-8 text-center'
+                                  className='h-8'
+                                  placeholder='localhost, .corp.example.com, 10.0.0.0/8'
                                   disabled={!enabled}
                                   {...field}
                                 />
                               </FormControl>
-                              <FormMessage className='absolute !mt-1' />
+                              <FormDescription>
+                                {t('Proxy-Bypass-Tip')}
+                              </FormDescription>
                             </FormItem>
                           )}
                         />
+                      )}
+                      <div className='flex items-center gap-2'>
+                        <Button
+                          variant='secondary'
+                          size='sm'
+                          loading={checking}
+                          onClick={onCheckProxy}
+                        >
+                          {t('Proxy-Check')}
+                        </Button>
+                        {usedProxy !== void 0 && (
+                          <FormDescription className='!mt-0 break-all'>
+                            {usedProxy === null
+                              ? t('Proxy-Direct')
+                              : t('Proxy-Used', { proxy: usedProxy })}
+                          </FormDescription>
+                        )}
                       </div>
                     </div>
                   </FormItem>
@@ -44,7 +44,7 @@ export async function loader() {
   try {
     const versions = await Promise.all([
       vCurrent(),
-      versionList(),
+      versionList().then(({ list }) => list),
       installedList(),
     ]);
 
@@ -346,7 +346,7 @@ export const Component: React.FC = () => {
     try {
       const [currentVersion, versions, installeds] = await Promise.all([
         vCurrent(true),
-        versionList(),
+        versionList().then(({ list }) => list),
         installedList(true),
       ]);
       setCurrent(currentVersion);
@@ -51,7 +51,7 @@ type VersionsResult = [string, Nvmd.Versions, Array<string>];
 export async function loader() {
   const versions = await Promise.all([
     vCurrent(),
-    versionList(),
+    versionList().then(({ list }) => list),
     installedList(),
   ]).catch(() => {
     return [[], [], ''];
@@ -369,7 +369,7 @@ export const Versions: React.FC = () => {
     try {
       const [currentVersion, versions, installeds] = await Promise.all([
         vCurrent(),
-        versionList(),
+        versionList().then(({ list }) => list),
         installedList(),
       ]);
       setCurrent(currentVersion);
@@ -386,7 +386,7 @@ export const Versions: React.FC = () => {
     try {
       const [currentVersion, versions, installeds] = await Promise.all([
         vCurrent(true),
-        versionList(true),
+        versionList(true).then(({ list }) => list),
         installedList(true),
       ]);
       setCurrent(currentVersion);
@@ -21,8 +21,14 @@ import {
 
 import { toast } from 'sonner';
 import { useTranslation } from 'react-i18next';
+import { formatBytes } from '@/lib/utils';
 import { getCurrent } from '@/services/api';
-import { installNode, installNodeCancel, vSetCurrent } from '@/services/cmds';
+import {
+  installJobs,
+  installNode,
+  installNodeCancel,
+  vSetCurrent,
+} from '@/services/cmds';
 
 export type ModalRef = {
   show: (data: Nvmd.Version) => void;
@@ -35,6 +41,25 @@ type ModalProps = {
 
 const archs = ['arm64', 'x64', 'x86'];
 
+const phases = {
+  resolving: 'Resolving',
+  connecting: 'Connecting',
+  verifying: 'Verifying',
+  finalizing: 'Finalizing',
+};
+
+// e.g. `12.3 MB / 45.6 MB · 2.1 MB/s · 16s`
+const formatTransfer = ({ done, total, rate, eta }: Nvmd.Transfer) =>
+  [
+    total === null
+      ? formatBytes(done)
+      : `${formatBytes(done)} / ${formatBytes(total)}`,
+    `${formatBytes(rate)}/s`,
+    eta === null ? null : `${eta}s`,
+  ]
+    .filter(Boolean)
+    .join(' · ');
+
 export const Modal: React.FC<ModalProps> = ({ ref, onRefrresh }) => {
   const [open, setOpen] = useState<boolean>(false);
   const [asDefault, setAsDefault] = useState<boolean>(true);
@@ -46,6 +71,7 @@ export const Modal: React.FC<ModalProps> = ({ ref, onRefrresh }) => {
   const arch = useRef<HTMLSpanElement>(null);
   const archOption = useRef<string[]>(archs);
   const progress = useRef<Nvmd.ProgressData>(undefined);
+  const job = useRef<number>(undefined);
 
   const { t } = useTranslation();
 
@@ -60,23 +86,11 @@ export const Modal: React.FC<ModalProps> = ({ ref, onRefrresh }) => {
     const unlisted = getCurrent().listen<Nvmd.ProgressData>(
       'on-node-progress',
       ({ payload }) => {
-        const { source, transferred, total } = payload;
-        if (source === 'download') {
-          progress.current = {
-            source,
-            transferred,
-            total,
-          };
-        }
-
-        if (source === 'unzip' && progress.current?.source !== 'unzip') {
-          progress.current = {
-            ...progress.current!,
-            source,
-            transferred: progress.current!.total,
-          };
-        }
+        // other installs may be running at the same time
+        if (payload.version !== record.current?.version.slice(1)) return;
 
+        job.current = payload.job;
+        progress.current = payload;
         updater((pre) => pre + 1);
       },
     );
@@ -111,11 +125,15 @@ export const Modal: React.FC<ModalProps> = ({ ref, onRefrresh }) => {
     setLoading(true);
     setPath(undefined);
     progress.current = undefined;
+    job.current = undefined;
     try {
-      const path = await installNode(
+      const { path, signature } = await installNode(
         record.current!.version.slice(1),
         arch.current!.innerText,
       );
+      if (signature.status === 'unverified') {
+        toast.warning(signature.reason);
+      }
 
       progress.current = {
         ...progress.current!,
@@ -132,7 +150,14 @@ export const Modal: React.FC<ModalProps> = ({ ref, onRefrresh }) => {
 
   const onAbort = async () => {
     try {
-      await installNodeCancel();
+      // a queued install has no progress yet
+      const id =
+        job.current ??
+        (await installJobs()).find(
+          ({ version }) => version === record.current?.version.slice(1),
+        )?.id;
+      if (id === undefined) return;
+      await installNodeCancel(id);
       progress.current = undefined;
       updater((pre) => pre + 1);
     } catch (err) {
@@ -183,18 +208,29 @@ export const Modal: React.FC<ModalProps> = ({ ref, onRefrresh }) => {
               <div className='flex items-center h-5'>
                 {progress.current ? (
                   <div className='flex flex-1 items-center space-x-2'>
-                    <Progress
-                      value={
-                        (progress.current.transferred /
-                          progress.current.total) *
-                        100
-                      }
-                      className='max-w-60'
-                    />
-                    {progress.current.source === 'unzip' ? (
-                      <Label>{t('Unzipping')}...</Label>
+                    {progress.current.phase === 'downloading' ||
+                    progress.current.phase === 'extracting' ? (
+                      <>
+                        <Progress
+                          value={
+                            progress.current.total
+                              ? (progress.current.done /
+                                  progress.current.total) *
+                                100
+                              : undefined
+                          }
+                          className='max-w-60'
+                        />
+                        {progress.current.phase === 'extracting' ? (
+                          <Label>{t('Unzipping')}...</Label>
+                        ) : (
+                          <Label>{formatTransfer(progress.current)}</Label>
+                        )}
+                      </>
+                    ) : progress.current.phase === 'retrying' ? (
+                      <Label>{`${t('Retrying')} (${progress.current.attempt}/${progress.current.retries})...`}</Label>
                     ) : (
-                      <Label>{`${progress.current.transferred} / ${progress.current.total} B`}</Label>
+                      <Label>{t(phases[progress.current.phase])}...</Label>
                     )}
                   </div>
                 ) : (
@@ -28,20 +28,31 @@ export function vCurrent(fetch: boolean = false) {
 
 /**
  * @description: Set the globally effective node version
- * @param {string} version node version number
+ * @param {string} version node version number or specifier (`lts/*`, `lts/iron`, `^18`, `20`, `latest`)
+ * @param {Nvmd.Prefer} prefer look for an installed or a released version first, defaults to `installed`
  * @return {Promise<void>}	Promise-void
  */
-export function vSetCurrent(version: string) {
-  return invoke<void>('set_current', { version });
+export function vSetCurrent(version: string, prefer?: Nvmd.Prefer) {
+  return invoke<void>('set_current', { version, prefer });
+}
+
+/**
+ * @description: Resolve a version specifier to a concrete version
+ * @param {string} version node version specifier (`lts/*`, `lts/iron`, `^18`, `20`, `latest`)
+ * @param {Nvmd.Prefer} prefer look for an installed or a released version first, defaults to `installed`
+ * @return {Promise<string>} node version number
+ */
+export function resolveVersion(version: string, prefer?: Nvmd.Prefer) {
+  return invoke<string>('resolve_version', { version, prefer });
 }
 
 /**
  * @description: Get a list of all officially released versions of node
- * @param {boolean} fetch Whether to pull new data from remote services
- * @return {Promise<Nvmd.Versions>}	List of all officially released versions of node
+ * @param {boolean} fetch Whether to pull new data from remote services, only downloaded when it changed
+ * @return {Promise<Nvmd.VersionList>}	List of all officially released versions of node, with its mirror and age
  */
 export function versionList(fetch: boolean = false) {
-  return invoke<Nvmd.Versions>('version_list', { fetch });
+  return invoke<Nvmd.VersionList>('version_list', { fetch });
 }
 
 /**
@@ -55,20 +66,140 @@ export function installedList(fetch: boolean = false) {
 
 /**
  * @description: Download Node
- * @param {string} version node version
+ * @param {string} version node version or specifier
  * @param {string} arch	node architecture
- * @return {Promise<string>}	The file path where the downloaded node is saved
+ * @param {Nvmd.Prefer} prefer look for an installed or a released version first, defaults to `remote`
+ * @param {number} downloadLimit download bandwidth of this install (KB/s), on top of the `download_limit` setting
+ * @return {Promise<Nvmd.InstallResult>}	The file path where the downloaded node is saved & the signature verification result
  */
-export function installNode(version: string, arch?: string) {
-  return invoke<string>('install_node', { version, arch });
+export function installNode(
+  version: string,
+  arch?: string,
+  prefer?: Nvmd.Prefer,
+  downloadLimit?: number,
+) {
+  return invoke<Nvmd.InstallResult>('install_node', {
+    version,
+    arch,
+    prefer,
+    downloadLimit,
+  });
 }
 
 /**
- * @description: Cancel the task of downloading node
+ * @description: Cancel an install job, running or queued
+ * @param {number} id job id, every install when omitted
  * @return {Promise<void>} Promise-void
  */
-export function installNodeCancel() {
-  return invoke<void>('install_node_cancel');
+export function installNodeCancel(id?: number) {
+  return invoke<void>('install_node_cancel', { id });
+}
+
+/**
+ * @description: Get the installs running & waiting in the queue
+ * @return {Promise<Array<Nvmd.Job>>} the running jobs first, then the queued ones in order
+ */
+export function installJobs() {
+  return invoke<Array<Nvmd.Job>>('install_jobs');
+}
+
+/**
+ * @description: Move a queued install in the queue
+ * @param {number} id job id
+ * @param {number} position its new place among the queued jobs, `0` to start it next
+ * @return {Promise<Array<Nvmd.Job>>} the jobs after the move
+ */
+export function installJobMove(id: number, position: number) {
+  return invoke<Array<Nvmd.Job>>('install_job_move', { id, position });
+}
+
+/**
+ * @description: Limit the download bandwidth of an install, a running download adjusts right away
+ * @param {number} id job id
+ * @param {number} downloadLimit KB/s, `0` or omitted for no limit of its own
+ * @return {Promise<Array<Nvmd.Job>>} the jobs after the change
+ */
+export function installJobLimit(id: number, downloadLimit?: number) {
+  return invoke<Array<Nvmd.Job>>('install_job_limit', { id, downloadLimit });
+}
+
+/**
+ * @description: Get the node archives in the cache
+ * @return {Promise<Array<Nvmd.CacheEntry>>} cached archives, the most recently used first
+ */
+export function cacheList() {
+  return invoke<Array<Nvmd.CacheEntry>>('cache_list');
+}
+
+/**
+ * @description: Download the node archive into the cache without installing it
+ * @param {string} version node version
+ * @param {string} arch	node architecture
+ * @param {number} downloadLimit download bandwidth of this download (KB/s), on top of the `download_limit` setting
+ * @return {Promise<Nvmd.CacheEntry>} the cached archive
+ */
+export function cacheDownload(
+  version: string,
+  arch?: string,
+  downloadLimit?: number,
+) {
+  return invoke<Nvmd.CacheEntry>('cache_download', {
+    version,
+    arch,
+    downloadLimit,
+  });
+}
+
+/**
+ * @description: Remove the least recently used archives until the cache fits in `maxSize`
+ * @param {number} maxSize maximum cache size (MB), defaults to the `cache_limit` setting, `0` clears the cache
+ * @return {Promise<Array<Nvmd.CacheEntry>>} the removed archives
+ */
+export function cachePrune(maxSize?: number) {
+  return invoke<Array<Nvmd.CacheEntry>>('cache_prune', { maxSize });
+}
+
+/**
+ * @description: Fetch the version index from every configured mirror at the same time
+ * @return {Promise<Array<Nvmd.MirrorBenchmark>>} the fastest mirror first, the failed ones last
+ */
+export function mirrorBenchmark() {
+  return invoke<Array<Nvmd.MirrorBenchmark>>('mirror_benchmark');
+}
+
+/**
+ * @description: Get the proxy a request to the url goes through with the current settings
+ * @param {string} url the url of the request
+ * @return {Promise<string | null>} the proxy url without its credentials, `null` when connecting directly
+ */
+export function proxyFor(url: string) {
+  return invoke<string | null>('proxy_for', { url });
+}
+
+/**
+ * @description: Get the leftovers of interrupted installs in the install directory
+ * @return {Promise<Array<Nvmd.Leftover>>} orphaned archives, staging and unrenamed directories
+ */
+export function leftoverList() {
+  return invoke<Array<Nvmd.Leftover>>('leftover_list');
+}
+
+/**
+ * @description: Remove leftovers of interrupted installs
+ * @param {string[]} paths the paths of the leftovers to remove
+ * @return {Promise<Array<Nvmd.Leftover>>} the remaining leftovers
+ */
+export function leftoverClean(paths: string[]) {
+  return invoke<Array<Nvmd.Leftover>>('leftover_clean', { paths });
+}
+
+/**
+ * @description: Finish interrupted installs by renaming the unpacked directories to their version
+ * @param {string[]} paths the paths of the finishable leftovers
+ * @return {Promise<Array<Nvmd.Leftover>>} the remaining leftovers
+ */
+export function leftoverFinish(paths: string[]) {
+  return invoke<Array<Nvmd.Leftover>>('leftover_finish', { paths });
 }
 
 /**
@@ -109,21 +240,35 @@ export function updateProjects(list: Nvmd.Project[], path?: string) {
 /**
  * @description: Update project version
  * @param {string} path project floder path
- * @param {string} version node version
+ * @param {string} version node version or specifier
+ * @param {Nvmd.Prefer} prefer look for an installed or a released version first, defaults to `installed`
  * @return {Promise<200 | 404>}
  */
-export function syncProjectVersion(path: string, version: string) {
-  return invoke<200 | 404>('sync_project_version', { path, version });
+export function syncProjectVersion(
+  path: string,
+  version: string,
+  prefer?: Nvmd.Prefer,
+) {
+  return invoke<200 | 404>('sync_project_version', { path, version, prefer });
 }
 
 /**
  * @description: Batch update project version
  * @param {string[]} paths project floder paths
- * @param {string} version node version
+ * @param {string} version node version or specifier
+ * @param {Nvmd.Prefer} prefer look for an installed or a released version first, defaults to `installed`
  * @return {Promise<void>}
  */
-export function batchUpdateProjectVersion(paths: string[], version: string) {
-  return invoke<void>('batch_update_project_version', { paths, version });
+export function batchUpdateProjectVersion(
+  paths: string[],
+  version: string,
+  prefer?: Nvmd.Prefer,
+) {
+  return invoke<void>('batch_update_project_version', {
+    paths,
+    version,
+    prefer,
+  });
 }
 
 /**
@@ -147,11 +292,16 @@ export function updateGroups(list: Nvmd.Group[]) {
 /**
  * @description: Update group version
  * @param {string} name group name
- * @param {string} version version
+ * @param {string} version version or specifier
+ * @param {Nvmd.Prefer} prefer look for an installed or a released version first, defaults to `installed`
  * @return {Promise<void>}
  */
-export function updateGroupVersion(name: string, version: string) {
-  return invoke<void>('update_group_version', { name, version });
+export function updateGroupVersion(
+  name: string,
+  version: string,
+  prefer?: Nvmd.Prefer,
+) {
+  return invoke<void>('update_group_version', { name, version, prefer });
 }
 
 /**